@@ -33,6 +33,21 @@ function App() {
     return () => clearTimeout(connectTimeout);
   }, [socket]);
 
+  useEffect(() => {
+    // A new connection, e.g. after a server restart, takes the seat back
+    const token = localStorage.getItem(`session:${roomCode}`);
+    if (!socket || roomCode === "" || !token) return;
+    const avatar = JSON.parse(localStorage.getItem("avatar") || "{}");
+    socket.emit("join_room", {
+      room_id: roomCode,
+      name: localStorage.getItem("username") || "",
+      avatar: avatar.avatar || "",
+      avatar_orientation: avatar.rotation || 0,
+      avatar_color: avatar.color || "",
+      token,
+    });
+  }, [socket]);
+
   useEffect(() => {
     const onPageLoad = () => {
       setIsLoaded(true);
@@ -23,6 +23,7 @@ const ChessBoard = () => {
   const [game, setGame] = useState(new Chess());
   const [selectMode, setSelectMode] = useState("");
   const [squareHighlight, setSquareHighlight] = useState<Square[]>([]);
+  const [removableSquares, setRemovableSquares] = useState<Square[]>([]);
   const [lastPosition, setLastPosition] = useState<string>(game.fen());
   const [check, setCheck] = useState<string>("x");
   const [color, setColor] = useState<string>("white");
@@ -92,7 +93,9 @@ const ChessBoard = () => {
       sfx.play("slide");
     };
 
-    const selectPieceListener = (piece: string) => {
+    const selectPieceListener = (piece: string, squares: Square[]) => {
+      // The server sends the squares that can legally be cleared
+      setRemovableSquares(squares);
       // Check for wildcards
       if (piece === "x") {
         toast({
@@ -131,6 +134,12 @@ const ChessBoard = () => {
       });
     };
 
+    const memoryTimeUpListener = () => {
+      // A pending removal is dropped when the memory phase runs out
+      setSelectMode("");
+      setRemovableSquares([]);
+    };
+
     const whiteListener = (id: string) => {
       if (id == socket?.id) {
         setColor("white");
@@ -142,19 +151,23 @@ const ChessBoard = () => {
     socket?.on("turn", turnListener);
     socket?.on("piece_moved", pieceMovedListener);
     socket?.on("game_reset", resetGameListener);
+    socket?.on("game_aborted", resetGameListener);
     socket?.on("select_piece", selectPieceListener);
     socket?.on("square_cleared", squareClearedListener);
     socket?.on("clear_failed", clearFailedListener);
     socket?.on("chess_board", chessBoardListener);
     socket?.on("white", whiteListener);
+    socket?.on("memory_time_up", memoryTimeUpListener);
 
     return () => {
       socket?.off("turn", turnListener);
       socket?.off("piece_moved", pieceMovedListener);
       socket?.off("game_reset", resetGameListener);
+      socket?.off("game_aborted", resetGameListener);
       socket?.off("select_piece", selectPieceListener);
       socket?.off("square_cleared", squareClearedListener);
       socket?.off("clear_failed", clearFailedListener);
+      socket?.off("memory_time_up", memoryTimeUpListener);
       socket?.off("chess_board", chessBoardListener);
       socket?.off("white", whiteListener);
     };
@@ -208,30 +221,8 @@ const ChessBoard = () => {
       setSquareHighlight([]);
       return;
     }
-    if (selectMode === "x") {
-      setSquareHighlight(
-        game
-          .board()
-          .flat()
-          .filter((sq) => {
-            if (sq?.type === "k") return false;
-            return true;
-          })
-          .map((sq) => sq?.square as Square),
-      );
-      return;
-    }
-    const pieceType = selectMode.charAt(1).toLowerCase();
-    const pieceColor = selectMode.charAt(0).toLowerCase();
-    const squares = game
-      .board()
-      .flat()
-      .filter((sq) => {
-        if (sq?.type === pieceType && sq?.color === pieceColor) return true;
-      })
-      .map((sq) => sq?.square as Square);
-    setSquareHighlight(squares);
-  }, [selectMode, game]);
+    setSquareHighlight(removableSquares);
+  }, [selectMode, removableSquares]);
 
   const handleMove = (
     from: Square,
@@ -287,7 +278,6 @@ const ChessBoard = () => {
     if (selectMode === "x") {
       if (piece.toLowerCase() === "k") return;
       socket!.emit("clear_square", square);
-      socket!.emit("match_piece", piece.toLowerCase());
       return;
     }
     if (piece.toUpperCase() == selectMode.substring(0, 2).toUpperCase()) {
@@ -1,6 +1,6 @@
 import { useEffect, useState } from "react";
 import { useSocket } from "../context/SocketProvider";
-import { IUserInfo } from "../types";
+import { IRatingDelta, IRoomInfo, IUserInfo, PlayerInfo } from "../types";
 import Header from "./Header";
 import Avatar from "./users/Avatar";
 import { RotateCw } from "react-feather";
@@ -8,10 +8,19 @@ import confetti from "canvas-confetti";
 import { gameOverConfettiOptions } from "../constants";
 import { useSFX } from "../context/SFXProvider";
 
+// e.g. "Alice 1512 (+12)", empty until the rating is updated
+const ratingLine = (player?: PlayerInfo, delta?: IRatingDelta) => {
+  if (!player || !delta) return "";
+  const sign = delta.change >= 0 ? "+" : "";
+  return `${player.name} ${delta.rating} (${sign}${delta.change})`;
+};
+
 const GameOver = ({ open }: { open: boolean }) => {
   const socket = useSocket();
   const [playerInfo, setPlayerInfo] = useState<IUserInfo>();
   const [result, setResult] = useState("");
+  const [rematch, setRematch] = useState<"" | "asked" | "offered">("");
+  const [roomInfo, setRoomInfo] = useState<IRoomInfo>();
   const sfx = useSFX();
 
   useEffect(() => {
@@ -30,26 +39,94 @@ const GameOver = ({ open }: { open: boolean }) => {
       setResult("Timeout");
     };
 
+    const abandonedListener = (info: IUserInfo) => {
+      setPlayerInfo(info);
+      setResult("Abandoned");
+    };
+
+    const resignationListener = (info: IUserInfo) => {
+      setPlayerInfo(info);
+      setResult("Resignation");
+    };
+
+    const drawAgreedListener = (info: IUserInfo) => {
+      setPlayerInfo(info);
+      setResult("Draw Agreed");
+    };
+
+    const repetitionListener = (info: IUserInfo) => {
+      setPlayerInfo(info);
+      setResult("Repetition");
+    };
+
+    const fiftyMovesListener = (info: IUserInfo) => {
+      setPlayerInfo(info);
+      setResult("Fifty Moves");
+    };
+
+    const insufficientMaterialListener = (info: IUserInfo) => {
+      setPlayerInfo(info);
+      setResult("Insufficient Material");
+    };
+
     const gameOverListener = (info: IUserInfo) => {
       setPlayerInfo(info);
       setResult("Game Over!");
     };
 
+    const rematchRequestedListener = (id: string) => {
+      setRematch(id === socket?.id ? "asked" : "offered");
+    };
+
+    const gameResetListener = () => {
+      setRematch("");
+    };
+
+    const roomInfoListener = (info?: IRoomInfo) => {
+      setRoomInfo(info);
+    };
+
     socket?.on("checkmate", checkmateListener);
     socket?.on("stalemate", stalemateListener);
     socket?.on("timeout", timeoutListener);
     socket?.on("game_over", gameOverListener);
+    socket?.on("abandoned", abandonedListener);
+    socket?.on("rematch_requested", rematchRequestedListener);
+    socket?.on("game_reset", gameResetListener);
+    socket?.on("room_info", roomInfoListener);
+    socket?.on("resignation", resignationListener);
+    socket?.on("draw_agreed", drawAgreedListener);
+    socket?.on("repetition", repetitionListener);
+    socket?.on("fifty_moves", fiftyMovesListener);
+    socket?.on("insufficient_material", insufficientMaterialListener);
 
     return () => {
       socket?.off("checkmate", checkmateListener);
       socket?.off("stalemate", stalemateListener);
       socket?.off("timeout", timeoutListener);
       socket?.off("game_over", gameOverListener);
+      socket?.off("abandoned", abandonedListener);
+      socket?.off("rematch_requested", rematchRequestedListener);
+      socket?.off("game_reset", gameResetListener);
+      socket?.off("room_info", roomInfoListener);
+      socket?.off("resignation", resignationListener);
+      socket?.off("draw_agreed", drawAgreedListener);
+      socket?.off("repetition", repetitionListener);
+      socket?.off("fifty_moves", fiftyMovesListener);
+      socket?.off("insufficient_material", insufficientMaterialListener);
     };
   }, [socket]);
 
-  const handleReset = () => {
-    socket?.emit("reset_game");
+  const isDraw = [
+    "Stalemate",
+    "Draw Agreed",
+    "Repetition",
+    "Fifty Moves",
+    "Insufficient Material",
+  ].includes(result);
+
+  const handleRematch = () => {
+    socket?.emit("rematch");
   };
 
   const handleConfetti = () => {
@@ -86,12 +163,14 @@ const GameOver = ({ open }: { open: boolean }) => {
   };
 
   useEffect(() => {
-    if (open && result !== "Stalemate") handleConfetti();
+    if (open && !isDraw) handleConfetti();
+    // The match score already includes this game
+    if (open) socket?.emit("room_info");
   }, [open]);
 
   useEffect(() => {
     if (open) {
-      if (result === "Stalemate") sfx.play("draw");
+      if (isDraw) sfx.play("draw");
       else {
         if (playerInfo?.player1 && playerInfo?.player1.id === socket?.id) {
           sfx.play("win");
@@ -120,9 +199,9 @@ const GameOver = ({ open }: { open: boolean }) => {
             </div>
             <div className="flex border-accent border rounded-lg pb-6 px-8 gap-4 pt-8">
               <div
-                className={`flex flex-col gap-2 relative ${result !== "Stalemate" && "scale-125"} items-center justify-center`}
+                className={`flex flex-col gap-2 relative ${!isDraw && "scale-125"} items-center justify-center`}
               >
-                {result !== "Stalemate" && (
+                {!isDraw && (
                   <p className="absolute -top-2 left-0 right-0 mx-auto w-fit bg-primary text-white z-10 text-xs p-0.5 rounded">
                     Winner
                   </p>
@@ -145,7 +224,7 @@ const GameOver = ({ open }: { open: boolean }) => {
                 VS
               </div>
               <div
-                className={`flex flex-col gap-2 ${result !== "Stalemate" && "opacity-75"} items-center justify-center`}
+                className={`flex flex-col gap-2 ${!isDraw && "opacity-75"} items-center justify-center`}
               >
                 <Avatar
                   avatar={
@@ -162,12 +241,31 @@ const GameOver = ({ open }: { open: boolean }) => {
                 </p>
               </div>
             </div>
+            {roomInfo?.rated && (
+              <p className="text-center text-sm">
+                {ratingLine(roomInfo.p1, roomInfo.rating_change.p1)}{" "}
+                {ratingLine(roomInfo.p2, roomInfo.rating_change.p2)}
+              </p>
+            )}
+            {roomInfo?.score && (
+              <p className="text-center font-semibold">
+                {roomInfo.p1?.name} {roomInfo.score.p1_wins} -{" "}
+                {roomInfo.score.p2_wins} {roomInfo.p2?.name}
+                {roomInfo.score.draws > 0 &&
+                  ` (${roomInfo.score.draws} drawn)`}
+              </p>
+            )}
             <button
               className="btn btn-primary text-white"
-              onClick={() => handleReset()}
+              disabled={rematch === "asked"}
+              onClick={() => handleRematch()}
             >
               <RotateCw color="white" />
-              Play Again
+              {rematch === "asked"
+                ? "Waiting for opponent..."
+                : rematch === "offered"
+                  ? "Accept Rematch"
+                  : "Rematch"}
             </button>
           </div>
         </div>
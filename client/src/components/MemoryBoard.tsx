@@ -62,14 +62,13 @@ const MemoryBoard = () => {
       );
     };
 
-    const flipTileListener = (i: number) => {
+    const flipTileListener = (i: number, value: string) => {
+      // Face-down tiles are hidden by the server, the value arrives with the flip
+      if (value === "x") handleConfetti(i, goldenConfettiOptions);
       setTiles((previousTiles) => {
-        if (previousTiles[i].value === "x")
-          handleConfetti(i, goldenConfettiOptions);
-
         return previousTiles.map((tile, index) => {
           if (index === i) {
-            return { ...tile, flipped: true };
+            return { ...tile, value, flipped: true };
           }
           return tile;
         });
@@ -127,26 +126,15 @@ const MemoryBoard = () => {
       });
     };
 
-    const resetListener = () => {
+    const memoryTimeUpListener = () => {
+      // The memory phase is over for this turn, only a chess move is left
+      setBoardLock(true);
       setFlips([]);
-      setBoardLock(false);
     };
 
-    const upgradeTileListener = (
-      ...tiles: { value: string; index: number }[]
-    ) => {
-      // Set the value of the tiles to the new value
-      setTiles((previousTiles) => {
-        return previousTiles.map((tile, index) => {
-          if (tiles.some((t) => t.index === index)) {
-            return {
-              ...tile,
-              value: tiles.find((t) => t.index === index)!.value,
-            };
-          }
-          return tile;
-        });
-      });
+    const resetListener = () => {
+      setFlips([]);
+      setBoardLock(false);
     };
 
     const pieceMovedListener = () => {
@@ -165,9 +153,10 @@ const MemoryBoard = () => {
     socket?.on("tiles_matched", matchTilesListener);
     socket?.on("turn", turnListener);
     socket?.on("game_reset", resetListener);
+    socket?.on("game_aborted", resetListener);
     socket?.on("remove_tiles", removeTilesListener);
-    socket?.on("upgrade_tile", upgradeTileListener);
     socket?.on("piece_moved", pieceMovedListener);
+    socket?.on("memory_time_up", memoryTimeUpListener);
 
     return () => {
       socket?.off("memory_board", memoryBoardListener);
@@ -176,9 +165,10 @@ const MemoryBoard = () => {
       socket?.off("tiles_matched", matchTilesListener);
       socket?.off("turn", turnListener);
       socket?.off("game_reset", resetListener);
+      socket?.off("game_aborted", resetListener);
       socket?.off("remove_tiles", removeTilesListener);
-      socket?.off("upgrade_tile", upgradeTileListener);
       socket?.off("piece_moved", pieceMovedListener);
+      socket?.off("memory_time_up", memoryTimeUpListener);
     };
   }, [socket]);
 
@@ -1,11 +1,12 @@
-import { Key, User } from "react-feather";
+import { Key, Save, User } from "react-feather";
 import Header from "../Header";
 import { useEffect, useReducer, useState } from "react";
 import AvatarCreator from "./AvatarCreator";
 import { AVATAR_COLORS } from "../../constants";
-import { IAvatar } from "../../types";
+import { IAccount, IAvatar, IProfile } from "../../types";
 import { useSocket } from "../../context/SocketProvider";
-import RoomType from "./RoomType";
+import RoomType, { TimeExtras } from "./RoomType";
+import ColorChoice, { ColorAssignment } from "./ColorChoice";
 import { useToaster } from "../../context/ToastProvider";
 
 interface AuthProps {
@@ -36,6 +37,14 @@ const Auth = ({ open, roomJoinedCallback }: AuthProps) => {
   );
   const [roomCode, setRoomCode] = useState("");
   const [roomTime, setRoomTime] = useState(10);
+  const [timeExtras, setTimeExtras] = useState<TimeExtras>({
+    increment: 0,
+    delay: 0,
+    maxMove: 0,
+  });
+  const [color, setColor] = useState<ColorAssignment>("random");
+  const [profile, setProfile] = useState<IProfile | null>(null);
+  const [rated, setRated] = useState(false);
   const [avatar, avatarDispatch] = useReducer<
     (state: IAvatar, action: string) => IAvatar
   >(
@@ -72,6 +81,8 @@ const Auth = ({ open, roomJoinedCallback }: AuthProps) => {
         avatar: avatar.avatar,
         avatar_orientation: avatar.rotation,
         avatar_color: avatar.color,
+        // Only the token from an earlier visit can take back a seat in the room
+        token: localStorage.getItem(`session:${roomCode}`) || undefined,
       });
       localStorage.setItem("avatar", JSON.stringify(avatar));
       localStorage.setItem("username", username);
@@ -105,17 +116,77 @@ const Auth = ({ open, roomJoinedCallback }: AuthProps) => {
       avatar: avatar.avatar,
       avatar_orientation: avatar.rotation,
       avatar_color: avatar.color,
-      time: roomTime > 0 ? roomTime * 60 : undefined,
+      time:
+        roomTime > 0
+          ? {
+              base: roomTime * 60,
+              increment: timeExtras.increment,
+              delay: timeExtras.delay || undefined,
+              max_move: timeExtras.maxMove || undefined,
+            }
+          : undefined,
+      color,
+      // Ratings only move when both players have accounts
+      rated: profile !== null && rated,
     });
     localStorage.setItem("avatar", JSON.stringify(avatar));
     localStorage.setItem("username", username);
   };
 
+  const saveProfile = () => {
+    if (username.length === 0 || avatar.avatar === "") {
+      toast({
+        content: "Please enter a username and an avatar",
+        duration: 3000,
+        type: "error",
+      });
+      return;
+    }
+    // Accounts are optional, saving one keeps the profile and stats
+    socket!.emit(profile ? "update_account" : "register", {
+      name: username,
+      avatar: avatar.avatar,
+      avatar_orientation: avatar.rotation,
+      avatar_color: avatar.color,
+    });
+  };
+
   useEffect(() => {
+    const accountToken = localStorage.getItem("account");
+    if (accountToken) socket?.emit("login", accountToken);
+
+    socket?.on("account", (account: IAccount, token: string) => {
+      localStorage.setItem("account", token);
+      setUsername(account.name);
+      avatarDispatch({
+        type: "SET_ALL",
+        payload: {
+          avatar: account.avatar,
+          color: account.avatar_color,
+          rotation: account.avatar_orientation,
+        },
+      });
+      socket?.emit("player_profile", {});
+    });
+    socket?.on("player_profile", (profile: IProfile | null) => {
+      setProfile(profile);
+    });
+    socket?.on("account_failed", (reason: string) => {
+      toast({
+        content: reason,
+        duration: 3000,
+        type: "error",
+      });
+    });
     socket?.on(
       "room_joined",
-      (roomCode: string, roomType: "casual" | "timed") => {
-        roomJoinedCallback(roomCode, roomType);
+      (roomCode: string, roomType: string, token: string) => {
+        localStorage.setItem(`session:${roomCode}`, token);
+        // Timed rooms are named after their time control, e.g. "timed 3+2"
+        roomJoinedCallback(
+          roomCode,
+          roomType.startsWith("timed") ? "timed" : "casual",
+        );
       },
     );
     socket?.on("join_failed", (reason: string) => {
@@ -127,6 +198,9 @@ const Auth = ({ open, roomJoinedCallback }: AuthProps) => {
     });
 
     return () => {
+      socket?.off("account");
+      socket?.off("player_profile");
+      socket?.off("account_failed");
       socket?.off("room_joined");
       socket?.off("join_failed");
     };
@@ -155,6 +229,19 @@ const Auth = ({ open, roomJoinedCallback }: AuthProps) => {
                   onChange={(e) => setUsername(e.target.value)}
                 />
               </label>
+              <button className="btn btn-outline btn-sm" onClick={saveProfile}>
+                <Save size={16} />
+                {profile ? "Update profile" : "Save profile"}
+              </button>
+              {profile && (
+                <p className="text-sm text-secondary">
+                  {profile.stats.games} games · {profile.stats.wins}W{" "}
+                  {profile.stats.losses}L {profile.stats.draws}D
+                  {profile.ratings.map(
+                    (r) => ` · ${r.pool} ${Math.round(r.rating)}`,
+                  )}
+                </p>
+              )}
             </div>
             <div className="divider divider-primary scale-125 divider-horizontal"></div>
             <div className="flex flex-col w-full border border-accent p-4 rounded-md">
@@ -181,7 +268,24 @@ const Auth = ({ open, roomJoinedCallback }: AuthProps) => {
                 OR
               </div>
               <div className="flex flex-col gap-2 items-center">
-                <RoomType roomTime={roomTime} setRoomTime={setRoomTime} />
+                <RoomType
+                  roomTime={roomTime}
+                  setRoomTime={setRoomTime}
+                  extras={timeExtras}
+                  setExtras={setTimeExtras}
+                />
+                <ColorChoice color={color} setColor={setColor} />
+                {profile && (
+                  <label className="label cursor-pointer gap-2 self-start">
+                    <input
+                      type="checkbox"
+                      className="checkbox checkbox-primary"
+                      checked={rated}
+                      onChange={(e) => setRated(e.target.checked)}
+                    />
+                    <span className="label-text">Rated game</span>
+                  </label>
+                )}
                 <button
                   className="btn btn-primary w-full text-white grow"
                   onClick={createRoom}
@@ -0,0 +1,34 @@
+export type ColorAssignment = "creator_white" | "random" | "creator_black";
+
+interface ColorChoiceProps {
+  color: ColorAssignment;
+  setColor: (color: ColorAssignment) => void;
+}
+
+const options: { value: ColorAssignment; label: string }[] = [
+  { value: "creator_white", label: "White" },
+  { value: "random", label: "Random" },
+  { value: "creator_black", label: "Black" },
+];
+
+const ColorChoice = ({ color, setColor }: ColorChoiceProps) => {
+  return (
+    <div
+      role="tablist"
+      className="tabs tabs-boxed shadow bg-secondary rounded-lg w-full"
+    >
+      {options.map((option) => (
+        <a
+          role="tab"
+          key={option.value}
+          className={`tab ${color === option.value && "tab-active"}`}
+          onClick={() => setColor(option.value)}
+        >
+          <p className="text-white font-semibold">{option.label}</p>
+        </a>
+      ))}
+    </div>
+  );
+};
+
+export default ColorChoice;
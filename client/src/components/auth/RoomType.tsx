@@ -1,12 +1,32 @@
 import { useEffect, useState } from "react";
 import { Moon, Watch } from "react-feather";
 
+// Extras of a timed room in seconds, 0 leaves them off
+export interface TimeExtras {
+  increment: number;
+  delay: number;
+  maxMove: number;
+}
+
 interface RoomTypeProps {
   roomTime: number;
   setRoomTime: (time: number) => void;
+  extras: TimeExtras;
+  setExtras: (extras: TimeExtras) => void;
 }
 
-const RoomType = ({ roomTime, setRoomTime }: RoomTypeProps) => {
+const extraFields: { key: keyof TimeExtras; label: string }[] = [
+  { key: "increment", label: "Increment" },
+  { key: "delay", label: "Delay" },
+  { key: "maxMove", label: "Move cap" },
+];
+
+const RoomType = ({
+  roomTime,
+  setRoomTime,
+  extras,
+  setExtras,
+}: RoomTypeProps) => {
   const [roomType, setRoomType] = useState<"casual" | "timed">("casual");
 
   useEffect(() => {
@@ -42,7 +62,7 @@ const RoomType = ({ roomTime, setRoomTime }: RoomTypeProps) => {
 
       <div
         role="tablist"
-        className={`tabs tabs-boxed bg-secondary rounded-t-none 
+        className={`tabs tabs-boxed bg-secondary rounded-none 
                     ${roomType === "timed" ? "h-12 p-2" : "h-0 p-0"} overflow-hidden 
                     w-full transition-all duration-500 ease-in-out`}
       >
@@ -75,6 +95,32 @@ const RoomType = ({ roomTime, setRoomTime }: RoomTypeProps) => {
           <p className="font-semibold text-white">30m</p>
         </a>
       </div>
+
+      <div
+        className={`flex gap-2 bg-secondary rounded-b-lg
+                    ${roomType === "timed" ? "h-16 px-2 pb-2" : "h-0 p-0"} overflow-hidden
+                    w-full transition-all duration-500 ease-in-out`}
+      >
+        {extraFields.map((field) => (
+          <label key={field.key} className="form-control w-1/3">
+            <span className="label-text text-white text-xs">
+              {field.label} (s)
+            </span>
+            <input
+              type="number"
+              min={0}
+              className="input input-bordered input-sm"
+              value={extras[field.key]}
+              onChange={(e) =>
+                setExtras({
+                  ...extras,
+                  [field.key]: Math.max(0, Number(e.target.value) || 0),
+                })
+              }
+            />
+          </label>
+        ))}
+      </div>
     </div>
   );
 };
@@ -1,6 +1,16 @@
-import { HelpCircle, Moon, RotateCw, Share, Sun } from "react-feather";
+import {
+  CornerUpLeft,
+  Flag,
+  HelpCircle,
+  Moon,
+  RotateCw,
+  Share,
+  Sun,
+  Users,
+} from "react-feather";
 import { useSocket } from "../../context/SocketProvider";
 import { useGameState } from "../../context/GameStateProvider";
+import { useToaster } from "../../context/ToastProvider";
 import { Dispatch, SetStateAction, useEffect, useState } from "react";
 import Header from "../Header";
 
@@ -10,7 +20,32 @@ const ControlsOverlay = ({
   setRoomCode: Dispatch<SetStateAction<string>>;
 }) => {
   const socket = useSocket();
-  const { setGameState } = useGameState();
+  const { gameState, setGameState } = useGameState();
+  const toast = useToaster();
+  const [drawOffered, setDrawOffered] = useState(false);
+  const [takebackRequested, setTakebackRequested] = useState(false);
+
+  const resign = () => {
+    socket?.emit("resign");
+  };
+
+  const offerDraw = () => {
+    socket?.emit("offer_draw");
+  };
+
+  const answerDraw = (accept: boolean) => {
+    setDrawOffered(false);
+    socket?.emit(accept ? "accept_draw" : "decline_draw");
+  };
+
+  const requestTakeback = () => {
+    socket?.emit("request_takeback");
+  };
+
+  const answerTakeback = (accept: boolean) => {
+    setTakebackRequested(false);
+    socket?.emit(accept ? "accept_takeback" : "decline_takeback");
+  };
 
   const resetGame = () => {
     socket?.emit("reset_game");
@@ -35,6 +70,129 @@ const ControlsOverlay = ({
     localStorage.setItem("darkmode", darkMode);
   }, [darkMode]);
 
+  useEffect(() => {
+    const drawOfferedListener = (id: string) => {
+      if (id === socket?.id) {
+        toast({ content: "Draw offered", duration: 3000, type: "success" });
+      } else {
+        setDrawOffered(true);
+      }
+    };
+
+    const drawClosedListener = () => {
+      setDrawOffered(false);
+    };
+
+    const drawDeclinedListener = (id: string) => {
+      setDrawOffered(false);
+      if (id !== socket?.id) {
+        toast({ content: "Draw declined", duration: 3000, type: "error" });
+      }
+    };
+
+    const drawFailedListener = (reason: string) => {
+      const messages: Record<string, string> = {
+        already_pending: "A draw offer is already open",
+        too_soon: "Make a few moves before offering again",
+        game_inactive: "No game in progress",
+      };
+      toast({
+        content: messages[reason] || "Can't do that right now",
+        duration: 3000,
+        type: "error",
+      });
+    };
+
+    const takebackRequestedListener = (id: string) => {
+      if (id === socket?.id) {
+        toast({
+          content: "Takeback requested",
+          duration: 3000,
+          type: "success",
+        });
+      } else {
+        setTakebackRequested(true);
+      }
+    };
+
+    const takebackClosedListener = () => {
+      setTakebackRequested(false);
+    };
+
+    const takebackDeclinedListener = (id: string) => {
+      setTakebackRequested(false);
+      if (id !== socket?.id) {
+        toast({
+          content: "Takeback declined",
+          duration: 3000,
+          type: "error",
+        });
+      }
+    };
+
+    const takebackFailedListener = (reason: string) => {
+      const messages: Record<string, string> = {
+        nothing_to_take_back: "You can only take back your own last move",
+        already_pending: "A takeback request is already open",
+        game_inactive: "No game in progress",
+      };
+      toast({
+        content: messages[reason] || "Can't do that right now",
+        duration: 3000,
+        type: "error",
+      });
+    };
+
+    const resetRequestedListener = (id: string) => {
+      toast({
+        content:
+          id === socket?.id
+            ? "Reset requested"
+            : "Your opponent wants to reset, press reset to agree",
+        duration: 3000,
+        type: "info",
+      });
+    };
+
+    const resetFailedListener = () => {
+      toast({
+        content: "Can't reset right now",
+        duration: 3000,
+        type: "error",
+      });
+    };
+
+    socket?.on("draw_offered", drawOfferedListener);
+    socket?.on("draw_declined", drawDeclinedListener);
+    socket?.on("draw_expired", drawClosedListener);
+    socket?.on("draw_failed", drawFailedListener);
+    socket?.on("game_reset", drawClosedListener);
+    socket?.on("takeback_requested", takebackRequestedListener);
+    socket?.on("takeback_declined", takebackDeclinedListener);
+    socket?.on("takeback_accepted", takebackClosedListener);
+    socket?.on("takeback_expired", takebackClosedListener);
+    socket?.on("takeback_failed", takebackFailedListener);
+    socket?.on("reset_requested", resetRequestedListener);
+    socket?.on("reset_failed", resetFailedListener);
+    socket?.on("game_reset", takebackClosedListener);
+
+    return () => {
+      socket?.off("draw_offered", drawOfferedListener);
+      socket?.off("draw_declined", drawDeclinedListener);
+      socket?.off("draw_expired", drawClosedListener);
+      socket?.off("draw_failed", drawFailedListener);
+      socket?.off("game_reset", drawClosedListener);
+      socket?.off("takeback_requested", takebackRequestedListener);
+      socket?.off("takeback_declined", takebackDeclinedListener);
+      socket?.off("takeback_accepted", takebackClosedListener);
+      socket?.off("takeback_expired", takebackClosedListener);
+      socket?.off("takeback_failed", takebackFailedListener);
+      socket?.off("reset_requested", resetRequestedListener);
+      socket?.off("reset_failed", resetFailedListener);
+      socket?.off("game_reset", takebackClosedListener);
+    };
+  }, [socket]);
+
   return (
     <div className="md:absolute right-5 md:top-5 flex gap-2">
       <div
@@ -75,6 +233,46 @@ const ControlsOverlay = ({
           <HelpCircle color="white" />
         </div>
       </div>
+      {gameState === "playing" && (
+        <>
+          <div
+            className="lg:tooltip lg:tooltip-bottom lg:tooltip-secondary"
+            data-tip="Take Back"
+          >
+            <div
+              className="flex p-2 md:p-4 rounded-xl bg-primary
+                gap-2 cursor-pointer"
+              onClick={() => requestTakeback()}
+            >
+              <CornerUpLeft color="white" />
+            </div>
+          </div>
+          <div
+            className="lg:tooltip lg:tooltip-bottom lg:tooltip-secondary"
+            data-tip="Offer Draw"
+          >
+            <div
+              className="flex p-2 md:p-4 rounded-xl bg-primary
+                gap-2 cursor-pointer"
+              onClick={() => offerDraw()}
+            >
+              <Users color="white" />
+            </div>
+          </div>
+          <div
+            className="lg:tooltip lg:tooltip-bottom lg:tooltip-secondary"
+            data-tip="Resign"
+          >
+            <div
+              className="flex p-2 md:p-4 rounded-xl bg-primary
+                gap-2 cursor-pointer"
+              onClick={() => resign()}
+            >
+              <Flag color="white" />
+            </div>
+          </div>
+        </>
+      )}
       <div
         className="lg:tooltip lg:tooltip-bottom lg:tooltip-secondary"
         data-tip="Reset Game"
@@ -99,6 +297,50 @@ const ControlsOverlay = ({
           <Share className="rotate-90" color="white" />
         </div>
       </div>
+      <dialog
+        id="draw_modal"
+        className="modal"
+        open={drawOffered && gameState === "playing"}
+      >
+        <div className="modal-box">
+          <h2 className="font-semibold text-xl mb-4">
+            Your opponent offers a draw
+          </h2>
+          <div className="flex gap-2 justify-end">
+            <button className="btn" onClick={() => answerDraw(false)}>
+              Decline
+            </button>
+            <button
+              className="btn btn-primary text-white"
+              onClick={() => answerDraw(true)}
+            >
+              Accept
+            </button>
+          </div>
+        </div>
+      </dialog>
+      <dialog
+        id="takeback_modal"
+        className="modal"
+        open={takebackRequested && gameState === "playing"}
+      >
+        <div className="modal-box">
+          <h2 className="font-semibold text-xl mb-4">
+            Your opponent asks to take back their last move
+          </h2>
+          <div className="flex gap-2 justify-end">
+            <button className="btn" onClick={() => answerTakeback(false)}>
+              Decline
+            </button>
+            <button
+              className="btn btn-primary text-white"
+              onClick={() => answerTakeback(true)}
+            >
+              Accept
+            </button>
+          </div>
+        </div>
+      </dialog>
       <dialog id="help_modal" className="modal" open={showHelp}>
         <div className="modal-box w-11/2 max-w-2xl no-scrollbar">
           <Header />
@@ -135,6 +377,11 @@ const ControlsOverlay = ({
           <ul className="list-disc list-inside">
             <li>A checkmate occurs.</li>
             <li>A stalemate occurs, resulting in a draw.</li>
+            <li>
+              The same position, memory board included, occurs three times, or
+              fifty moves go by without a pawn move, capture or memory match.
+              Both result in a draw.
+            </li>
             <li>
               All pieces of one player, except the King, are eliminated. The
               other player is declared the winner.
@@ -143,6 +390,7 @@ const ControlsOverlay = ({
               In timed mode, if a player runs out of time, the other player is
               declared the winner.
             </li>
+            <li>A player resigns, or both players agree to a draw.</li>
           </ul>
           <h2 className="font-semibold text-xl mt-6 mb-2">Tips</h2>
           <ul className="list-disc list-inside">
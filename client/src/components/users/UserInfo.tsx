@@ -12,8 +12,11 @@ const UserInfo = ({ roomType }: UserInfoProps) => {
   const socket = useSocket();
   const [info, setInfo] = useState<IUserInfo>();
   const [turn, setTurn] = useState("");
+  // Times are kept in milliseconds, as sent by the server
   const [p1Time, setP1Time] = useState(0);
   const [p2Time, setP2Time] = useState(0);
+  // Seconds the opponent has left to come back to a suspended game
+  const [abandonCountdown, setAbandonCountdown] = useState(0);
   const { gameState } = useGameState();
   const countdownRef = useRef<NodeJS.Timeout | null>(null);
 
@@ -31,9 +34,15 @@ const UserInfo = ({ roomType }: UserInfoProps) => {
 
     const playerInfoListener = (newInfo: IUserInfo) => {
       setInfo(newInfo);
+      setAbandonCountdown(0);
+    };
+    const abandonCountdownListener = (seconds: number) => {
+      // The server sends the last second before awarding the game, so clear it then
+      setAbandonCountdown(seconds > 1 ? seconds : 0);
     };
     const turnListener = (turn: string, times: number[]) => {
       setTurn(turn);
+      setAbandonCountdown(0);
       if (roomType === "casual") return;
       setP1Time(times[0]);
       setP2Time(times[1]);
@@ -52,6 +61,7 @@ const UserInfo = ({ roomType }: UserInfoProps) => {
     socket?.on("game_reset", resetListener);
     socket?.on("opponent_disconnected", disconnectListener);
     socket?.on("player_times", playerTimesListener);
+    socket?.on("abandon_countdown", abandonCountdownListener);
 
     return () => {
       socket?.off("room_joined", roomJoinedListener);
@@ -60,6 +70,7 @@ const UserInfo = ({ roomType }: UserInfoProps) => {
       socket?.off("game_reset", resetListener);
       socket?.off("opponent_disconnected", disconnectListener);
       socket?.off("player_times", playerTimesListener);
+      socket?.off("abandon_countdown", abandonCountdownListener);
     };
   }, [socket, roomType]);
 
@@ -75,11 +86,11 @@ const UserInfo = ({ roomType }: UserInfoProps) => {
     } else {
       countdownRef.current = setInterval(() => {
         if (turn === info?.player1?.id) {
-          setP1Time((prev) => prev - 1);
+          setP1Time((prev) => prev - 100);
         } else if (turn === info?.player2?.id) {
-          setP2Time((prev) => prev - 1);
+          setP2Time((prev) => prev - 100);
         }
-      }, 1000);
+      }, 100);
     }
 
     return () => {
@@ -108,11 +119,16 @@ const UserInfo = ({ roomType }: UserInfoProps) => {
             avatar: info.player1.avatar,
           }
         }
-        time={roomType === "timed" ? p1Time : undefined}
+        time={roomType === "timed" ? Math.ceil(p1Time / 1000) : undefined}
         connected={info?.player1?.connected}
         turn={turn == info?.player1?.id}
       />
       <div className="divider divider-accent">VS</div>
+      {abandonCountdown > 0 && (
+        <p className="text-center text-sm text-error">
+          Opponent has {abandonCountdown}s to come back
+        </p>
+      )}
       <UserCard
         username={info?.player2?.name || ""}
         avatar={
@@ -124,7 +140,7 @@ const UserInfo = ({ roomType }: UserInfoProps) => {
         }
         you={socket?.id === info?.player2?.id}
         turn={turn == info?.player2?.id}
-        time={roomType === "timed" ? p2Time : undefined}
+        time={roomType === "timed" ? Math.ceil(p2Time / 1000) : undefined}
         connected={info?.player2?.connected}
         reverse
       />
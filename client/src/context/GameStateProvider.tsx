@@ -55,6 +55,12 @@ export const GameStateProvider = ({ children }: { children: ReactNode }) => {
     socket?.on("checkmate", gameOverListener);
     socket?.on("timeout", gameOverListener);
     socket?.on("game_over", gameOverListener);
+    socket?.on("abandoned", gameOverListener);
+    socket?.on("resignation", gameOverListener);
+    socket?.on("draw_agreed", gameOverListener);
+    socket?.on("repetition", gameOverListener);
+    socket?.on("fifty_moves", gameOverListener);
+    socket?.on("insufficient_material", gameOverListener);
     socket?.on("game_reset", resetListener);
     socket?.on("opponent_disconnected", disconnectListener);
     socket?.on("room_full", roomFullListener);
@@ -66,6 +72,12 @@ export const GameStateProvider = ({ children }: { children: ReactNode }) => {
       socket?.off("checkmate", gameOverListener);
       socket?.off("timeout", gameOverListener);
       socket?.off("game_over", gameOverListener);
+      socket?.off("abandoned", gameOverListener);
+      socket?.off("resignation", gameOverListener);
+      socket?.off("draw_agreed", gameOverListener);
+      socket?.off("repetition", gameOverListener);
+      socket?.off("fifty_moves", gameOverListener);
+      socket?.off("insufficient_material", gameOverListener);
       socket?.off("game_reset", resetListener);
       socket?.off("opponent_disconnected", disconnectListener);
       socket?.off("room_full", roomFullListener);
@@ -32,6 +32,58 @@ interface IUserInfo {
   player2: PlayerInfo | undefined;
 }
 
+interface IMatchScore {
+  p1_wins: number;
+  p2_wins: number;
+  draws: number;
+}
+
+interface IRatingDelta {
+  rating: number;
+  change: number;
+}
+
+interface IRoomInfo {
+  p1?: PlayerInfo;
+  p2?: PlayerInfo;
+  score: IMatchScore;
+  rated: boolean;
+  rating_change: {
+    p1?: IRatingDelta;
+    p2?: IRatingDelta;
+  };
+}
+
+interface IAccount {
+  id: number;
+  name: string;
+  avatar: string;
+  avatar_orientation: number;
+  avatar_color: string;
+  created_at: number;
+}
+
+interface IPlayerStats {
+  games: number;
+  wins: number;
+  losses: number;
+  draws: number;
+}
+
+interface IPoolRating {
+  pool: "casual" | "timed";
+  rating: number;
+  deviation: number;
+  volatility: number;
+  games: number;
+}
+
+interface IProfile {
+  account: IAccount;
+  stats: IPlayerStats;
+  ratings: IPoolRating[];
+}
+
 interface HistoryEntry {
   player: string;
   board: "memory" | "chess";
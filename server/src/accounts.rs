@@ -0,0 +1,727 @@
+use std::{
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rand::{thread_rng, Rng};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chess::util::GameResult,
+    rating::{Rating, RatingDelta, RatingPool},
+    util::database_path,
+};
+
+// Tables of the account database, created on first use
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS accounts (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL,
+        avatar TEXT NOT NULL,
+        avatar_orientation INTEGER NOT NULL,
+        avatar_color TEXT NOT NULL,
+        created_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS games (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        player1 INTEGER REFERENCES accounts(id),
+        player2 INTEGER REFERENCES accounts(id),
+        result TEXT NOT NULL,
+        draw INTEGER NOT NULL,
+        played_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS ratings (
+        account INTEGER NOT NULL REFERENCES accounts(id),
+        pool TEXT NOT NULL,
+        rating REAL NOT NULL,
+        deviation REAL NOT NULL,
+        volatility REAL NOT NULL,
+        games INTEGER NOT NULL,
+        PRIMARY KEY (account, pool)
+    );
+    CREATE TABLE IF NOT EXISTS settings (
+        key TEXT PRIMARY KEY,
+        value BLOB NOT NULL
+    );
+";
+
+// Changes made to the tables since, applied in order and counted in the user_version pragma
+const MIGRATIONS: [&str; 1] = ["
+    ALTER TABLE games ADD COLUMN moves INTEGER NOT NULL DEFAULT 0;
+    CREATE INDEX ratings_by_pool ON ratings (pool, rating DESC);
+    CREATE INDEX games_by_player1 ON games (player1);
+    CREATE INDEX games_by_player2 ON games (player2);
+"];
+
+// Top of a pool, in the order of the ratings_by_pool index so no rating is scanned past the limit
+const LEADERBOARD: &str = "
+    SELECT a.id, a.name, a.avatar, a.avatar_orientation, a.avatar_color, a.created_at,
+           r.rating, r.deviation, r.volatility, r.games
+    FROM ratings r JOIN accounts a ON a.id = r.account
+    WHERE r.pool = ?1 ORDER BY r.rating DESC LIMIT ?2
+";
+
+// Leaderboards and game history are served in pages of at most this many rows
+pub const MAX_PAGE_SIZE: u32 = 100;
+pub const DEFAULT_PAGE_SIZE: u32 = 20;
+
+// Durable player identities, kept in SQLite so they outlive the rooms they play in
+// Accounts are optional, games between two anonymous players are not recorded
+pub struct AccountStore {
+    db: Mutex<Connection>,
+}
+
+// Account a socket is logged into, kept in the socket extensions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AccountId(pub i64);
+
+// Display name and avatar settings, as sent by the auth screen
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AccountDetails {
+    pub name: String,
+    pub avatar: String,
+    pub avatar_orientation: u8,
+    pub avatar_color: String,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Account {
+    pub id: i64,
+    #[serde(flatten)]
+    pub details: AccountDetails,
+    pub created_at: i64, // unix seconds
+}
+
+// Lifetime stats of an account, over every recorded game
+#[derive(Debug, Clone, Copy, Serialize, Default, PartialEq)]
+pub struct PlayerStats {
+    pub games: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+// Rating of an account in one pool, pools it never played in are left out
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct PoolRating {
+    pub pool: RatingPool,
+    #[serde(flatten)]
+    pub rating: Rating,
+    pub games: u32,
+}
+
+// A place on the leaderboard of a pool
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct LeaderboardEntry {
+    pub rank: u32,
+    pub account: Account,
+    #[serde(flatten)]
+    pub rating: Rating,
+    pub games: u32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Win,
+    Loss,
+    Draw,
+}
+
+// A recorded game as seen by one of its players, anonymous opponents have no id or name
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct GameRecord {
+    pub id: i64,
+    pub opponent: Option<i64>,
+    pub opponent_name: Option<String>,
+    pub result: String,
+    pub outcome: Outcome,
+    pub moves: u32, // chess moves played by both players
+    pub played_at: i64,
+}
+
+// One page of a player's games, newest first
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct GamePage {
+    pub games: Vec<GameRecord>,
+    pub page: u32,
+    pub per_page: u32,
+    pub total: u32,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Profile {
+    pub account: Account,
+    pub stats: PlayerStats,
+    pub ratings: Vec<PoolRating>,
+}
+
+impl AccountStore {
+    pub fn open(path: &str) -> rusqlite::Result<Self> {
+        Self::init(Connection::open(path)?)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> rusqlite::Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    pub fn from_env() -> rusqlite::Result<Self> {
+        Self::open(&database_path())
+    }
+
+    fn init(mut db: Connection) -> rusqlite::Result<Self> {
+        db.execute_batch(SCHEMA)?;
+        let version: usize = db.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            let tx = db.transaction()?;
+            tx.execute_batch(migration)?;
+            tx.pragma_update(None, "user_version", i + 1)?;
+            tx.commit()?;
+        }
+        Ok(Self { db: Mutex::new(db) })
+    }
+
+    pub fn signing_key(&self) -> rusqlite::Result<Vec<u8>> {
+        // Returns the key tokens are signed with, generated once and kept so tokens survive a restart
+        let db = self.db.lock().unwrap();
+        let key: Option<Vec<u8>> = db
+            .query_row(
+                "SELECT value FROM settings WHERE key = 'signing_key'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(key) = key {
+            return Ok(key);
+        }
+        let key = thread_rng().gen::<[u8; 32]>().to_vec();
+        db.execute(
+            "INSERT INTO settings (key, value) VALUES ('signing_key', ?1)",
+            params![key],
+        )?;
+        Ok(key)
+    }
+
+    pub fn create(&self, details: AccountDetails) -> rusqlite::Result<Account> {
+        // Creates a new account and returns it
+        let db = self.db.lock().unwrap();
+        let created_at = now();
+        db.execute(
+            "INSERT INTO accounts (name, avatar, avatar_orientation, avatar_color, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                details.name,
+                details.avatar,
+                details.avatar_orientation,
+                details.avatar_color,
+                created_at
+            ],
+        )?;
+        Ok(Account {
+            id: db.last_insert_rowid(),
+            details,
+            created_at,
+        })
+    }
+
+    pub fn update(&self, id: i64, details: AccountDetails) -> rusqlite::Result<Option<Account>> {
+        // Changes the name and avatar of an account, returns None if it doesn't exist
+        let changed = self.db.lock().unwrap().execute(
+            "UPDATE accounts SET name = ?2, avatar = ?3, avatar_orientation = ?4, avatar_color = ?5
+             WHERE id = ?1",
+            params![
+                id,
+                details.name,
+                details.avatar,
+                details.avatar_orientation,
+                details.avatar_color
+            ],
+        )?;
+        if changed == 0 {
+            return Ok(None);
+        }
+        self.get(id)
+    }
+
+    pub fn get(&self, id: i64) -> rusqlite::Result<Option<Account>> {
+        self.db
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT id, name, avatar, avatar_orientation, avatar_color, created_at
+                 FROM accounts WHERE id = ?1",
+                params![id],
+                |row| {
+                    Ok(Account {
+                        id: row.get(0)?,
+                        details: AccountDetails {
+                            name: row.get(1)?,
+                            avatar: row.get(2)?,
+                            avatar_orientation: row.get(3)?,
+                            avatar_color: row.get(4)?,
+                        },
+                        created_at: row.get(5)?,
+                    })
+                },
+            )
+            .optional()
+    }
+
+    pub fn get_stats(&self, id: i64) -> rusqlite::Result<PlayerStats> {
+        // Counts the recorded games of an account, player1 of a decisive game is its winner
+        self.db.lock().unwrap().query_row(
+            "SELECT
+                 COUNT(*),
+                 COALESCE(SUM(draw = 0 AND player1 = ?1), 0),
+                 COALESCE(SUM(draw = 0 AND player2 = ?1), 0),
+                 COALESCE(SUM(draw = 1), 0)
+             FROM games WHERE player1 = ?1 OR player2 = ?1",
+            params![id],
+            |row| {
+                Ok(PlayerStats {
+                    games: row.get(0)?,
+                    wins: row.get(1)?,
+                    losses: row.get(2)?,
+                    draws: row.get(3)?,
+                })
+            },
+        )
+    }
+
+    pub fn get_profile(&self, id: i64) -> rusqlite::Result<Option<Profile>> {
+        // Returns an account with its lifetime stats
+        let Some(account) = self.get(id)? else {
+            return Ok(None);
+        };
+        Ok(Some(Profile {
+            account,
+            stats: self.get_stats(id)?,
+            ratings: self.get_ratings(id)?,
+        }))
+    }
+
+    pub fn get_ratings(&self, id: i64) -> rusqlite::Result<Vec<PoolRating>> {
+        // Returns the ratings of an account in every pool it played rated games in
+        let db = self.db.lock().unwrap();
+        let mut statement = db.prepare(
+            "SELECT pool, rating, deviation, volatility, games FROM ratings
+             WHERE account = ?1 ORDER BY pool",
+        )?;
+        let ratings = statement
+            .query_map(params![id], |row| {
+                let pool: String = row.get(0)?;
+                Ok(PoolRating {
+                    pool: match pool.as_str() {
+                        "timed" => RatingPool::Timed,
+                        _ => RatingPool::Casual,
+                    },
+                    rating: Rating {
+                        rating: row.get(1)?,
+                        deviation: row.get(2)?,
+                        volatility: row.get(3)?,
+                    },
+                    games: row.get(4)?,
+                })
+            })?
+            .collect();
+        ratings
+    }
+
+    pub fn rate_game(
+        &self,
+        pool: RatingPool,
+        p1: i64,
+        p2: i64,
+        score: f64,
+    ) -> rusqlite::Result<(RatingDelta, RatingDelta)> {
+        // Updates both ratings after a rated game, score is player 1's, and returns the changes
+        // Each game is its own rating period, so ratings move as soon as the game ends
+        let mut db = self.db.lock().unwrap();
+        let tx = db.transaction()?;
+        let load = |account: i64| {
+            tx.query_row(
+                "SELECT rating, deviation, volatility FROM ratings WHERE account = ?1 AND pool = ?2",
+                params![account, pool.as_str()],
+                |row| {
+                    Ok(Rating {
+                        rating: row.get(0)?,
+                        deviation: row.get(1)?,
+                        volatility: row.get(2)?,
+                    })
+                },
+            )
+            .optional()
+            .map(Option::unwrap_or_default)
+        };
+        let (before1, before2) = (load(p1)?, load(p2)?);
+        let after1 = before1.update(&[(before2, score)]);
+        let after2 = before2.update(&[(before1, 1.0 - score)]);
+        for (account, rating) in [(p1, after1), (p2, after2)] {
+            tx.execute(
+                "INSERT INTO ratings (account, pool, rating, deviation, volatility, games)
+                 VALUES (?1, ?2, ?3, ?4, ?5, 1)
+                 ON CONFLICT (account, pool) DO UPDATE SET
+                     rating = ?3, deviation = ?4, volatility = ?5, games = games + 1",
+                params![
+                    account,
+                    pool.as_str(),
+                    rating.rating,
+                    rating.deviation,
+                    rating.volatility
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok((after1.delta_from(&before1), after2.delta_from(&before2)))
+    }
+
+    pub fn record_game(&self, result: &GameResult, moves: u32) -> rusqlite::Result<bool> {
+        // Records a finished game, returns false if it doesn't count for any account
+        // A game between two anonymous players, or an account and itself, isn't recorded
+        let (p1, p2) = (result.player1.get_account(), result.player2.get_account());
+        if (p1.is_none() && p2.is_none()) || p1 == p2 {
+            return Ok(false);
+        }
+        self.db.lock().unwrap().execute(
+            "INSERT INTO games (player1, player2, result, draw, moves, played_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![p1, p2, result.result, result.is_draw(), moves, now()],
+        )?;
+        Ok(true)
+    }
+
+    pub fn leaderboard(
+        &self,
+        pool: RatingPool,
+        limit: u32,
+    ) -> rusqlite::Result<Vec<LeaderboardEntry>> {
+        // Returns the highest rated accounts of a pool, read in order from the ratings_by_pool index
+        let db = self.db.lock().unwrap();
+        let mut statement = db.prepare(LEADERBOARD)?;
+        let entries = statement
+            .query_map(params![pool.as_str(), limit.min(MAX_PAGE_SIZE)], |row| {
+                Ok((
+                    Account {
+                        id: row.get(0)?,
+                        details: AccountDetails {
+                            name: row.get(1)?,
+                            avatar: row.get(2)?,
+                            avatar_orientation: row.get(3)?,
+                            avatar_color: row.get(4)?,
+                        },
+                        created_at: row.get(5)?,
+                    },
+                    Rating {
+                        rating: row.get(6)?,
+                        deviation: row.get(7)?,
+                        volatility: row.get(8)?,
+                    },
+                    row.get(9)?,
+                ))
+            })?
+            .zip(1..)
+            .map(|(entry, rank)| {
+                let (account, rating, games) = entry?;
+                Ok(LeaderboardEntry {
+                    rank,
+                    account,
+                    rating,
+                    games,
+                })
+            })
+            .collect();
+        entries
+    }
+
+    pub fn player_games(&self, id: i64, page: u32, per_page: u32) -> rusqlite::Result<GamePage> {
+        // Returns a page of the games of an account, newest first, pages start at 0
+        let per_page = per_page.clamp(1, MAX_PAGE_SIZE);
+        let total = self.get_stats(id)?.games;
+        let db = self.db.lock().unwrap();
+        let mut statement = db.prepare(
+            "SELECT g.id, g.player1, g.player2, g.result, g.draw, g.moves, g.played_at, a.name
+             FROM games g
+             LEFT JOIN accounts a
+                 ON a.id = CASE WHEN g.player1 = ?1 THEN g.player2 ELSE g.player1 END
+             WHERE g.player1 = ?1 OR g.player2 = ?1
+             ORDER BY g.id DESC LIMIT ?2 OFFSET ?3",
+        )?;
+        let games = statement
+            .query_map(
+                params![id, per_page, u64::from(page) * u64::from(per_page)],
+                |row| {
+                    let (p1, p2): (Option<i64>, Option<i64>) = (row.get(1)?, row.get(2)?);
+                    let won = p1 == Some(id);
+                    let outcome = match (row.get(4)?, won) {
+                        (true, _) => Outcome::Draw,
+                        (false, true) => Outcome::Win,
+                        (false, false) => Outcome::Loss,
+                    };
+                    Ok(GameRecord {
+                        id: row.get(0)?,
+                        opponent: if won { p2 } else { p1 },
+                        opponent_name: row.get(7)?,
+                        result: row.get(3)?,
+                        outcome,
+                        moves: row.get(5)?,
+                        played_at: row.get(6)?,
+                    })
+                },
+            )?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(GamePage {
+            games,
+            page,
+            per_page,
+            total,
+        })
+    }
+}
+
+fn now() -> i64 {
+    // Returns the current unix time in seconds
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::{
+        chess::util::is_game_over,
+        room::{color::ColorAssignment, Room, RoomType},
+        user::User,
+    };
+
+    fn details(name: &str) -> AccountDetails {
+        AccountDetails {
+            name: name.to_string(),
+            avatar: ":)".to_string(),
+            avatar_orientation: 90,
+            avatar_color: "#fff".to_string(),
+        }
+    }
+
+    fn result(p1: Option<i64>, p2: Option<i64>, result: &str) -> GameResult {
+        let mut player1 = User::default();
+        let mut player2 = User::default();
+        player1.set_account(p1);
+        player2.set_account(p2);
+        GameResult {
+            player1,
+            player2,
+            result: result.to_string(),
+        }
+    }
+
+    fn king_only_win(alice: i64, bob: i64) -> GameResult {
+        // Alice plays white and takes all of Bob's pieces but his king
+        let mut room = Room::new(
+            "p1".to_string(),
+            "alice".to_string(),
+            ":)".to_string(),
+            0,
+            "#fff".to_string(),
+            RoomType::Casual,
+            0,
+        );
+        room.set_color_assignment(ColorAssignment::CreatorWhite);
+        room.connect_player(
+            "p2".to_string(),
+            "bob".to_string(),
+            ":(".to_string(),
+            0,
+            "#000".to_string(),
+            None,
+        );
+        room.set_account("p1", Some(alice));
+        room.set_account("p2", Some(bob));
+        let board = chess::Board::from_str("4k3/8/8/8/8/8/8/Q3K3 b - - 0 1").unwrap();
+        room.set_chess_board(board);
+        is_game_over(&board, &room).unwrap()
+    }
+
+    #[test]
+    fn accounts_round_trip() {
+        let store = AccountStore::open_in_memory().unwrap();
+        let account = store.create(details("alice")).unwrap();
+        assert_eq!(store.get(account.id).unwrap(), Some(account.clone()));
+
+        let updated = store.update(account.id, details("bob")).unwrap().unwrap();
+        assert_eq!(updated.details.name, "bob");
+        assert_eq!(updated.created_at, account.created_at);
+        assert_eq!(store.update(account.id + 1, details("eve")).unwrap(), None);
+        assert_eq!(store.get(account.id + 1).unwrap(), None);
+    }
+
+    #[test]
+    fn stats_count_recorded_games() {
+        let store = AccountStore::open_in_memory().unwrap();
+        let alice = store.create(details("alice")).unwrap().id;
+        let bob = store.create(details("bob")).unwrap().id;
+
+        assert!(store
+            .record_game(&result(Some(alice), Some(bob), "checkmate"), 0)
+            .unwrap());
+        assert!(store
+            .record_game(&result(None, Some(alice), "timeout"), 0)
+            .unwrap());
+        assert!(store
+            .record_game(&result(Some(bob), Some(alice), "stalemate"), 0)
+            .unwrap());
+        assert!(!store
+            .record_game(&result(None, None, "checkmate"), 0)
+            .unwrap());
+        assert!(!store
+            .record_game(&result(Some(bob), Some(bob), "checkmate"), 0)
+            .unwrap());
+
+        let profile = store.get_profile(alice).unwrap().unwrap();
+        assert_eq!(
+            profile.stats,
+            PlayerStats {
+                games: 3,
+                wins: 1,
+                losses: 1,
+                draws: 1,
+            }
+        );
+        assert_eq!(store.get_stats(bob).unwrap().games, 2);
+    }
+
+    #[test]
+    fn king_only_wins_count_for_the_winner() {
+        let store = AccountStore::open_in_memory().unwrap();
+        let alice = store.create(details("alice")).unwrap().id;
+        let bob = store.create(details("bob")).unwrap().id;
+
+        assert!(store.record_game(&king_only_win(alice, bob), 0).unwrap());
+        assert_eq!(store.get_stats(alice).unwrap().wins, 1);
+        assert_eq!(store.get_stats(bob).unwrap().losses, 1);
+        let history = store.player_games(alice, 0, DEFAULT_PAGE_SIZE).unwrap();
+        assert_eq!(history.games[0].outcome, Outcome::Win);
+        assert_eq!(history.games[0].opponent, Some(bob));
+    }
+
+    #[test]
+    fn rated_games_move_ratings_in_their_pool() {
+        let store = AccountStore::open_in_memory().unwrap();
+        let alice = store.create(details("alice")).unwrap().id;
+        let bob = store.create(details("bob")).unwrap().id;
+
+        let (winner, loser) = store.rate_game(RatingPool::Timed, alice, bob, 1.0).unwrap();
+        assert!(winner.change > 0);
+        assert_eq!(winner.change, -loser.change);
+        assert_eq!(winner.rating, 1500 + winner.change);
+
+        // The second game starts from the new ratings and more certain deviations
+        let (second, _) = store.rate_game(RatingPool::Timed, alice, bob, 1.0).unwrap();
+        assert!(second.change > 0 && second.change < winner.change);
+
+        let ratings = store.get_ratings(alice).unwrap();
+        assert_eq!(ratings.len(), 1);
+        assert_eq!(ratings[0].pool, RatingPool::Timed);
+        assert_eq!(ratings[0].games, 2);
+        assert_eq!(ratings[0].rating.rating.round() as i32, second.rating);
+        assert!(store.get_ratings(bob).unwrap()[0].rating.rating < 1500.0);
+    }
+
+    #[test]
+    fn king_only_wins_are_rated_for_the_winner() {
+        let store = AccountStore::open_in_memory().unwrap();
+        let alice = store.create(details("alice")).unwrap().id;
+        let bob = store.create(details("bob")).unwrap().id;
+
+        // The winner comes first, as record_game rates it
+        let result = king_only_win(alice, bob);
+        assert_eq!(result.player1.get_account(), Some(alice));
+        let (winner, loser) = (result.player1.get_account(), result.player2.get_account());
+        store
+            .rate_game(RatingPool::Casual, winner.unwrap(), loser.unwrap(), 1.0)
+            .unwrap();
+        assert!(store.get_ratings(alice).unwrap()[0].rating.rating > 1500.0);
+        assert!(store.get_ratings(bob).unwrap()[0].rating.rating < 1500.0);
+    }
+
+    #[test]
+    fn leaderboards_rank_each_pool() {
+        let store = AccountStore::open_in_memory().unwrap();
+        let alice = store.create(details("alice")).unwrap().id;
+        let bob = store.create(details("bob")).unwrap().id;
+        let carol = store.create(details("carol")).unwrap().id;
+        store.rate_game(RatingPool::Timed, alice, bob, 1.0).unwrap();
+        store
+            .rate_game(RatingPool::Casual, carol, alice, 1.0)
+            .unwrap();
+
+        let timed = store.leaderboard(RatingPool::Timed, 10).unwrap();
+        let ranks: Vec<_> = timed.iter().map(|e| (e.rank, e.account.id)).collect();
+        assert_eq!(ranks, vec![(1, alice), (2, bob)]);
+        assert_eq!(timed[0].games, 1);
+
+        let casual = store.leaderboard(RatingPool::Casual, 1).unwrap();
+        assert_eq!(casual.len(), 1);
+        assert_eq!(casual[0].account.details.name, "carol");
+    }
+
+    #[test]
+    fn leaderboards_are_read_from_the_index() {
+        let store = AccountStore::open_in_memory().unwrap();
+        let db = store.db.lock().unwrap();
+        let mut statement = db
+            .prepare(&format!("EXPLAIN QUERY PLAN {}", LEADERBOARD))
+            .unwrap();
+        let plan: Vec<String> = statement
+            .query_map(params!["timed", 10], |row| row.get(3))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert!(plan.iter().any(|step| step.contains("ratings_by_pool")));
+        assert!(!plan.iter().any(|step| step.contains("TEMP B-TREE")));
+    }
+
+    #[test]
+    fn game_history_comes_in_pages() {
+        let store = AccountStore::open_in_memory().unwrap();
+        let alice = store.create(details("alice")).unwrap().id;
+        let bob = store.create(details("bob")).unwrap().id;
+        store
+            .record_game(&result(Some(alice), Some(bob), "checkmate"), 30)
+            .unwrap();
+        store
+            .record_game(&result(Some(bob), Some(alice), "resignation"), 12)
+            .unwrap();
+        store
+            .record_game(&result(None, Some(alice), "stalemate"), 41)
+            .unwrap();
+
+        let first = store.player_games(alice, 0, 2).unwrap();
+        assert_eq!(first.total, 3);
+        let outcomes: Vec<_> = first.games.iter().map(|g| g.outcome).collect();
+        assert_eq!(outcomes, vec![Outcome::Draw, Outcome::Loss]);
+        assert_eq!(first.games[0].opponent, None);
+        assert_eq!(first.games[0].moves, 41);
+        assert_eq!(first.games[1].opponent_name.as_deref(), Some("bob"));
+
+        let second = store.player_games(alice, 1, 2).unwrap();
+        assert_eq!(second.games.len(), 1);
+        assert_eq!(second.games[0].outcome, Outcome::Win);
+        assert_eq!(second.games[0].opponent, Some(bob));
+        assert_eq!(second.games[0].result, "checkmate");
+        assert!(store.player_games(alice, 2, 2).unwrap().games.is_empty());
+    }
+
+    #[test]
+    fn the_signing_key_is_kept() {
+        let store = AccountStore::open_in_memory().unwrap();
+        let key = store.signing_key().unwrap();
+        assert_eq!(key.len(), 32);
+        assert_eq!(store.signing_key().unwrap(), key);
+    }
+}
@@ -1,7 +1,13 @@
-use chess::{BoardStatus, Color};
+use chess::{BoardBuilder, BoardStatus, CastleRights, ChessMove, Color, Piece, Square, EMPTY};
 use tracing::error;
 
-use crate::{room::Room, user::User};
+use crate::{
+    room::{
+        removal::{RemovalEntitlement, RemovalError},
+        Room,
+    },
+    user::User,
+};
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct Move {
@@ -17,29 +23,77 @@ pub struct GameResult {
     pub result: String,
 }
 
-pub fn is_game_over(board: &chess::Board, room: &Room) -> Option<GameResult> {
-    if let Some(winner) = room.check_win() {
-        let (p1, p2) = room.get_players();
-        let p1 = p1.unwrap();
-        let p2 = p2.unwrap();
+// Results that end a game without a winner
+const DRAWS: [&str; 5] = [
+    "stalemate",
+    "draw_agreed",
+    "repetition",
+    "fifty_moves",
+    "insufficient_material",
+];
 
-        let winner = match winner {
-            Color::White => "White",
-            Color::Black => "Black",
+impl Move {
+    pub fn to_chess_move(&self) -> Option<ChessMove> {
+        // Parses the move as sent by a client, None if a square is malformed
+        let from = self.from.parse::<Square>().ok()?;
+        let to = self.to.parse::<Square>().ok()?;
+        let promotion = match self.promotion.as_str() {
+            "q" => Some(Piece::Queen),
+            "r" => Some(Piece::Rook),
+            "b" => Some(Piece::Bishop),
+            "n" => Some(Piece::Knight),
+            _ => None,
         };
+        Some(ChessMove::new(from, to, promotion))
+    }
+}
 
-        Some(if p1.get_chess_color() == winner {
-            GameResult {
-                player1: p1,
-                player2: p2,
-                result: "game_over".to_string(),
-            }
-        } else {
-            GameResult {
-                player1: p2,
-                player2: p1,
-                result: "game_over".to_string(),
-            }
+impl GameResult {
+    pub fn is_draw(&self) -> bool {
+        // Returns true if nobody won, player1 is the winner otherwise
+        DRAWS.contains(&self.result.as_str())
+    }
+}
+
+pub fn count_pieces(board: &chess::Board, piece: Piece, color: Color) -> usize {
+    // Returns how many pieces of the given kind and color are on the board
+    (board.pieces(piece) & board.color_combined(color)).popcnt() as usize
+}
+
+pub fn captured_piece(board: &chess::Board, chess_move: ChessMove) -> Option<(Piece, Color)> {
+    // Returns the piece a legal move captures, if any
+    let dest = chess_move.get_dest();
+    if let (Some(piece), Some(color)) = (board.piece_on(dest), board.color_on(dest)) {
+        return Some((piece, color));
+    }
+
+    // En passant lands on an empty square behind the captured pawn
+    let ep = board.en_passant()?;
+    let source = chess_move.get_source();
+    if board.piece_on(source) == Some(Piece::Pawn)
+        && source.get_rank() == ep.get_rank()
+        && dest.get_file() == ep.get_file()
+    {
+        return Some((Piece::Pawn, !board.side_to_move()));
+    }
+    None
+}
+
+pub fn is_game_over(board: &chess::Board, room: &Room) -> Option<GameResult> {
+    if let Some(winner) = room.check_win() {
+        // One side only has a king left
+        let (Some(white), Some(black)) = (room.get_white(), room.get_black()) else {
+            error!("Game over but missing player");
+            return None;
+        };
+        let (player1, player2) = match winner {
+            Color::White => (white, black),
+            Color::Black => (black, white),
+        };
+        Some(GameResult {
+            player1,
+            player2,
+            result: "game_over".to_string(),
         })
     } else if board.status() == BoardStatus::Checkmate {
         // Checkmate
@@ -64,16 +118,227 @@ pub fn is_game_over(board: &chess::Board, room: &Room) -> Option<GameResult> {
             },
         })
     } else if board.status() == BoardStatus::Stalemate {
-        // Stalemate
-        let (p1, p2) = room.get_players();
-        let p1 = p1.unwrap();
-        let p2 = p2.unwrap();
-        Some(GameResult {
-            player1: p1,
-            player2: p2,
-            result: "stalemate".to_string(),
-        })
+        draw(room, "stalemate")
+    } else if room.is_repetition() {
+        draw(room, "repetition")
+    } else if room.is_fifty_moves() {
+        draw(room, "fifty_moves")
+    } else if insufficient_material(board) {
+        draw(room, "insufficient_material")
     } else {
         None
     }
 }
+
+fn draw(room: &Room, result: &str) -> Option<GameResult> {
+    // Builds a drawn result with the players in room order
+    let (p1, p2) = room.get_players();
+    Some(GameResult {
+        player1: p1?,
+        player2: p2?,
+        result: result.to_string(),
+    })
+}
+
+pub fn insufficient_material(board: &chess::Board) -> bool {
+    // Returns true if neither side can ever checkmate
+    // A side left with only its king has already lost, so this only draws games where both
+    // sides keep a piece, i.e. nothing but bishops that all stand on squares of one color
+    let bishops = *board.pieces(Piece::Bishop);
+    if *board.combined() != bishops | *board.pieces(Piece::King) {
+        return false;
+    }
+    let light = bishops
+        .filter(|sq| (sq.get_rank().to_index() + sq.get_file().to_index()) % 2 == 1)
+        .count();
+    light == 0 || light == bishops.popcnt() as usize
+}
+
+pub fn remove_piece(board: &chess::Board, square: Square) -> Result<chess::Board, RemovalError> {
+    // Removes the piece on the given square without breaking chess rules
+    let color = board.color_on(square).ok_or(RemovalError::EmptySquare)?;
+    if board.piece_on(square) == Some(Piece::King) {
+        return Err(RemovalError::King);
+    }
+
+    let mut builder = BoardBuilder::from(board);
+
+    // A rook removed from its corner takes the castle rights on that side with it
+    let rights = builder
+        .get_castle_rights(color)
+        .remove(CastleRights::square_to_castle_rights(color, square));
+    builder.castle_rights(color, rights);
+
+    // A pawn that just moved two squares can no longer be taken en passant
+    if board.en_passant() == Some(square) {
+        builder.en_passant(None);
+    }
+
+    builder.clear_square(square);
+
+    // Fails if the opponent is left in check while it is still the mover's turn
+    let new_board: chess::Board = (&builder)
+        .try_into()
+        .map_err(|_| RemovalError::IllegalPosition)?;
+
+    // The removal can't open a new line of attack on the mover's own king
+    if *new_board.checkers() & !*board.checkers() != EMPTY {
+        return Err(RemovalError::ExposesKing);
+    }
+
+    Ok(new_board)
+}
+
+pub fn legal_removals(board: &chess::Board, entitlement: &RemovalEntitlement) -> Vec<Square> {
+    // Returns every square the entitlement can legally clear
+    board
+        .combined()
+        .filter(|sq| {
+            let piece = board.piece_on(*sq).unwrap();
+            let color = board.color_on(*sq).unwrap();
+            entitlement.check(piece, color).is_ok() && remove_piece(board, *sq).is_ok()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn board(fen: &str) -> chess::Board {
+        chess::Board::from_str(fen).unwrap()
+    }
+
+    #[test]
+    fn malformed_moves_are_rejected_before_reaching_the_room() {
+        let parse = |from: &str, to: &str| {
+            Move {
+                from: from.to_string(),
+                to: to.to_string(),
+                promotion: String::new(),
+            }
+            .to_chess_move()
+        };
+        assert_eq!(
+            parse("e2", "e4"),
+            Some(ChessMove::new(Square::E2, Square::E4, None))
+        );
+        assert_eq!(parse("e9", "e4"), None);
+        assert_eq!(parse("e2", ""), None);
+    }
+
+    #[test]
+    fn bishops_on_one_color_cant_mate() {
+        assert!(insufficient_material(&board(
+            "4k3/8/3b4/8/8/2B5/8/4K3 w - - 0 1"
+        )));
+        assert!(insufficient_material(&board(
+            "2b1k3/8/8/8/8/8/8/3BK2B w - - 0 1"
+        )));
+        // Opposite colored bishops can still mate in a corner
+        assert!(!insufficient_material(&board(
+            "4k3/8/2b5/8/8/2B5/8/4K3 w - - 0 1"
+        )));
+        assert!(!insufficient_material(&board(
+            "4k3/8/2n5/8/8/2B5/8/4K3 w - - 0 1"
+        )));
+        assert!(!insufficient_material(&chess::Board::default()));
+    }
+
+    #[test]
+    fn captures_are_found_on_the_destination_square() {
+        let b = board("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1");
+        let take = ChessMove::new(Square::E4, Square::D5, None);
+        assert_eq!(captured_piece(&b, take), Some((Piece::Pawn, Color::Black)));
+        let push = ChessMove::new(Square::E4, Square::E5, None);
+        assert_eq!(captured_piece(&b, push), None);
+    }
+
+    #[test]
+    fn en_passant_captures_the_passed_pawn() {
+        let b = board("rnbqkbnr/1pp1pppp/p7/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3");
+        let ep = ChessMove::new(Square::E5, Square::D6, None);
+        assert!(b.legal(ep));
+        assert_eq!(captured_piece(&b, ep), Some((Piece::Pawn, Color::Black)));
+        let push = ChessMove::new(Square::E5, Square::E6, None);
+        assert_eq!(captured_piece(&b, push), None);
+    }
+
+    #[test]
+    fn removing_a_pinned_piece_of_your_own_exposes_the_king() {
+        // White knight on e2 shields the white king from the rook on e8
+        let b = board("k3r3/8/8/8/8/8/4N3/4K3 w - - 0 1");
+        assert_eq!(remove_piece(&b, Square::E2), Err(RemovalError::ExposesKing));
+    }
+
+    #[test]
+    fn removing_the_opponents_shield_leaves_them_in_check() {
+        // Black knight on e7 shields the black king from the rook on e1, white to move
+        let b = board("4k3/4n3/8/8/8/8/8/K3R3 w - - 0 1");
+        assert_eq!(
+            remove_piece(&b, Square::E7),
+            Err(RemovalError::IllegalPosition)
+        );
+    }
+
+    #[test]
+    fn removing_a_checking_piece_is_allowed() {
+        let b = board("k3r3/8/8/8/8/8/8/4K3 w - - 0 1");
+        let new_board = remove_piece(&b, Square::E8).unwrap();
+        assert_eq!(*new_board.checkers(), EMPTY);
+    }
+
+    #[test]
+    fn kings_and_empty_squares_cant_be_removed() {
+        let b = chess::Board::default();
+        assert_eq!(remove_piece(&b, Square::E1), Err(RemovalError::King));
+        assert_eq!(remove_piece(&b, Square::E4), Err(RemovalError::EmptySquare));
+    }
+
+    #[test]
+    fn removing_a_corner_rook_drops_that_castle_side() {
+        let b = chess::Board::default();
+        let new_board = remove_piece(&b, Square::A1).unwrap();
+        assert_eq!(
+            new_board.castle_rights(Color::White),
+            CastleRights::KingSide
+        );
+        assert_eq!(new_board.castle_rights(Color::Black), CastleRights::Both);
+
+        let new_board = remove_piece(&new_board, Square::H8).unwrap();
+        assert_eq!(
+            new_board.castle_rights(Color::Black),
+            CastleRights::QueenSide
+        );
+    }
+
+    #[test]
+    fn removing_an_en_passant_pawn_clears_the_target() {
+        // Black just played d7-d5 next to the white pawn on e5
+        let b = board("rnbqkbnr/1pp1pppp/p7/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3");
+        assert_eq!(b.en_passant(), Some(Square::D5));
+        let new_board = remove_piece(&b, Square::D5).unwrap();
+        assert_eq!(new_board.en_passant(), None);
+    }
+
+    #[test]
+    fn legal_removals_follow_the_entitlement() {
+        let b = chess::Board::default();
+        let queen = RemovalEntitlement::Piece("bq".parse().unwrap());
+        assert_eq!(legal_removals(&b, &queen), vec![Square::D8]);
+
+        let any = legal_removals(&b, &RemovalEntitlement::AnyNonKing);
+        assert_eq!(any.len(), 30);
+        assert!(!any.contains(&Square::E1));
+        assert!(!any.contains(&Square::E8));
+    }
+
+    #[test]
+    fn legal_removals_skip_pinned_shields() {
+        let b = board("k3r3/8/8/8/8/8/4N3/4K1N1 w - - 0 1");
+        let knights = RemovalEntitlement::Piece("wn".parse().unwrap());
+        assert_eq!(legal_removals(&b, &knights), vec![Square::G1]);
+    }
+}
@@ -0,0 +1,262 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use tokio::time::{Duration, Instant};
+
+// Time control of a timed room, all values are in seconds
+// increment is added after every move (Fischer), delay refunds up to that much of each move
+// (Bronstein) and max_move flags a player who spends longer than that on a single move
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub struct TimeControl {
+    pub base: u64,
+    #[serde(default)]
+    pub increment: u64,
+    #[serde(default)]
+    pub delay: Option<u64>,
+    #[serde(default)]
+    pub max_move: Option<u64>,
+}
+
+// A player's chess clock
+// remaining is the time banked when the clock was last stopped, turn_start is set while it runs
+// Time left is computed on demand from the Instant the turn started, so it never drifts
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Clock {
+    control: TimeControl,
+    remaining: Duration,
+    turn_start: Option<Instant>,
+}
+
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Standard notation, e.g. "3+2" for increment and "5|3" for delay
+        // The base is written in minutes when it is a whole number of them
+        if self.base.is_multiple_of(60) {
+            write!(f, "{}", self.base / 60)?;
+        } else {
+            write!(f, "{}s", self.base)?;
+        }
+        match self.delay {
+            Some(delay) if self.increment > 0 => write!(f, "+{}|{}", self.increment, delay)?,
+            Some(delay) => write!(f, "|{}", delay)?,
+            None => write!(f, "+{}", self.increment)?,
+        }
+        if let Some(max_move) = self.max_move {
+            write!(f, " max {}s", max_move)?;
+        }
+        Ok(())
+    }
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Self {
+        Self {
+            control,
+            remaining: Duration::from_secs(control.base),
+            turn_start: None,
+        }
+    }
+
+    pub fn start(&mut self) {
+        // Starts counting down, does nothing if the clock is already running
+        if self.turn_start.is_none() {
+            self.turn_start = Some(Instant::now());
+        }
+    }
+
+    pub fn stop(&mut self) {
+        // Stops counting down and banks the time used this turn
+        self.remaining = self.banked();
+        self.turn_start = None;
+    }
+
+    pub fn complete_move(&mut self) {
+        // Stops the clock at the end of a move and applies the increment and delay
+        // A player who has already run out of time gets nothing back
+        let used = self.used();
+        self.stop();
+        if self.remaining.is_zero() {
+            return;
+        }
+        let delay = Duration::from_secs(self.control.delay.unwrap_or(0));
+        self.remaining += Duration::from_secs(self.control.increment) + used.min(delay);
+    }
+
+    pub fn reset(&mut self) {
+        // Stops the clock and sets it back to the base time
+        self.remaining = Duration::from_secs(self.control.base);
+        self.turn_start = None;
+    }
+
+    pub fn set_remaining(&mut self, remaining: Duration) {
+        // Stops the clock and sets the time left, used to roll a turn back
+        self.remaining = remaining;
+        self.turn_start = None;
+    }
+
+    pub fn remaining(&self) -> Duration {
+        // Returns the time left, counting the running turn and the per-move cap
+        let left = self.remaining.saturating_sub(self.used());
+        match (self.turn_start, self.control.max_move) {
+            (Some(_), Some(max_move)) => {
+                left.min(Duration::from_secs(max_move).saturating_sub(self.used()))
+            }
+            _ => left,
+        }
+    }
+
+    pub fn remaining_ms(&self) -> u64 {
+        // Returns the time left in whole milliseconds
+        self.remaining().as_millis() as u64
+    }
+
+    fn used(&self) -> Duration {
+        // Returns the time spent on the running turn
+        self.turn_start
+            .map(|start| start.elapsed())
+            .unwrap_or_default()
+    }
+
+    fn banked(&self) -> Duration {
+        // Returns the time left once the running turn is taken off, zero if the player flagged
+        if self.remaining().is_zero() {
+            Duration::ZERO
+        } else {
+            self.remaining.saturating_sub(self.used())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::time::advance;
+
+    use super::*;
+
+    fn sudden_death(base: u64) -> TimeControl {
+        TimeControl {
+            base,
+            ..TimeControl::default()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn counts_down_only_while_running() {
+        let mut clock = Clock::new(sudden_death(60));
+        advance(Duration::from_secs(5)).await;
+        assert_eq!(clock.remaining_ms(), 60_000);
+
+        clock.start();
+        advance(Duration::from_millis(1_250)).await;
+        assert_eq!(clock.remaining_ms(), 58_750);
+
+        clock.stop();
+        advance(Duration::from_secs(10)).await;
+        assert_eq!(clock.remaining_ms(), 58_750);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn turns_add_up_without_drift() {
+        let mut clock = Clock::new(sudden_death(60));
+        for _ in 0..40 {
+            clock.start();
+            advance(Duration::from_millis(333)).await;
+            clock.stop();
+        }
+        assert_eq!(clock.remaining_ms(), 60_000 - 40 * 333);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn never_goes_below_zero() {
+        let mut clock = Clock::new(sudden_death(1));
+        clock.start();
+        clock.start();
+        advance(Duration::from_secs(3)).await;
+        assert_eq!(clock.remaining_ms(), 0);
+        clock.stop();
+        assert_eq!(clock.remaining(), Duration::ZERO);
+
+        clock.reset();
+        assert_eq!(clock.remaining_ms(), 1_000);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn increment_is_added_after_each_move() {
+        let mut clock = Clock::new(TimeControl {
+            base: 180,
+            increment: 2,
+            ..TimeControl::default()
+        });
+        clock.start();
+        advance(Duration::from_millis(500)).await;
+        clock.complete_move();
+        assert_eq!(clock.remaining_ms(), 181_500);
+
+        // Pausing the clock is not a move
+        clock.start();
+        advance(Duration::from_secs(1)).await;
+        clock.stop();
+        assert_eq!(clock.remaining_ms(), 180_500);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn delay_refunds_at_most_the_delay() {
+        let mut clock = Clock::new(TimeControl {
+            base: 300,
+            delay: Some(3),
+            ..TimeControl::default()
+        });
+        clock.start();
+        advance(Duration::from_secs(2)).await;
+        clock.complete_move();
+        assert_eq!(clock.remaining_ms(), 300_000);
+
+        clock.start();
+        advance(Duration::from_secs(10)).await;
+        clock.complete_move();
+        assert_eq!(clock.remaining_ms(), 293_000);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn move_cap_flags_a_slow_move() {
+        let mut clock = Clock::new(TimeControl {
+            base: 300,
+            increment: 5,
+            max_move: Some(30),
+            ..TimeControl::default()
+        });
+        clock.start();
+        advance(Duration::from_secs(10)).await;
+        assert_eq!(clock.remaining_ms(), 20_000);
+        clock.complete_move();
+        assert_eq!(clock.remaining_ms(), 295_000);
+
+        clock.start();
+        advance(Duration::from_secs(30)).await;
+        assert_eq!(clock.remaining_ms(), 0);
+        clock.complete_move();
+        assert_eq!(clock.remaining_ms(), 0);
+    }
+
+    #[test]
+    fn time_controls_use_standard_notation() {
+        let blitz = TimeControl {
+            base: 180,
+            increment: 2,
+            ..TimeControl::default()
+        };
+        assert_eq!(blitz.to_string(), "3+2");
+        let delay = TimeControl {
+            base: 300,
+            delay: Some(3),
+            ..TimeControl::default()
+        };
+        assert_eq!(delay.to_string(), "5|3");
+        let capped = TimeControl {
+            base: 90,
+            max_move: Some(20),
+            ..TimeControl::default()
+        };
+        assert_eq!(capped.to_string(), "90s+0 max 20s");
+    }
+}
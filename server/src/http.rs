@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use tracing::error;
+
+use crate::{
+    accounts::{AccountStore, GamePage, LeaderboardEntry, DEFAULT_PAGE_SIZE},
+    rating::RatingPool,
+};
+
+// Query string of GET /leaderboard/:pool
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct LeaderboardQuery {
+    limit: Option<u32>,
+}
+
+// Query string of GET /players/:id/games, pages start at 0
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct GamesQuery {
+    #[serde(default)]
+    page: u32,
+    per_page: Option<u32>,
+}
+
+pub fn routes(accounts: Arc<AccountStore>) -> Router {
+    // Read-only routes for pages that aren't connected to a game, e.g. a public leaderboard
+    Router::new()
+        .route("/leaderboard/:pool", get(leaderboard))
+        .route("/players/:id/games", get(player_games))
+        .with_state(accounts)
+}
+
+async fn leaderboard(
+    State(accounts): State<Arc<AccountStore>>,
+    Path(pool): Path<RatingPool>,
+    Query(query): Query<LeaderboardQuery>,
+) -> Result<Json<Vec<LeaderboardEntry>>, StatusCode> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    accounts.leaderboard(pool, limit).map(Json).map_err(|e| {
+        error!("Failed to load leaderboard: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+async fn player_games(
+    State(accounts): State<Arc<AccountStore>>,
+    Path(id): Path<i64>,
+    Query(query): Query<GamesQuery>,
+) -> Result<Json<GamePage>, StatusCode> {
+    let internal = |e| {
+        error!("Failed to load games of account {}: {}", id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    if accounts.get(id).map_err(internal)?.is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let per_page = query.per_page.unwrap_or(DEFAULT_PAGE_SIZE);
+    accounts
+        .player_games(id, query.page, per_page)
+        .map(Json)
+        .map_err(internal)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::accounts::AccountDetails;
+
+    async fn get_json(app: Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let response = app
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn routes_serve_leaderboards_and_history() {
+        let accounts = Arc::new(AccountStore::open_in_memory().unwrap());
+        let details = |name: &str| AccountDetails {
+            name: name.to_string(),
+            avatar: ":)".to_string(),
+            avatar_orientation: 0,
+            avatar_color: "#fff".to_string(),
+        };
+        let alice = accounts.create(details("alice")).unwrap().id;
+        let bob = accounts.create(details("bob")).unwrap().id;
+        accounts
+            .rate_game(RatingPool::Casual, alice, bob, 1.0)
+            .unwrap();
+        let app = routes(accounts);
+
+        let (status, body) = get_json(app.clone(), "/leaderboard/casual?limit=1").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body[0]["account"]["name"], "alice");
+        assert_eq!(body.as_array().unwrap().len(), 1);
+
+        let (status, _) = get_json(app.clone(), "/leaderboard/blitz").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, body) = get_json(app.clone(), &format!("/players/{}/games", bob)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["total"], 0);
+
+        let (status, _) = get_json(app, "/players/99/games").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -6,9 +6,15 @@ use tracing::info;
 
 use socket::state::SocketState;
 
+mod accounts;
 mod chess;
+mod clock;
+mod http;
 mod memory;
+mod rating;
 mod room;
+mod session;
+mod snapshots;
 mod socket;
 mod user;
 mod util;
@@ -17,6 +23,7 @@ mod util;
 pub async fn axum() -> shuttle_axum::ShuttleAxum {
     // State for the socket server
     let rooms = SocketState::new().await;
+    let accounts = rooms.accounts.clone();
 
     // Create a SocketIO server layer with the state
     let (layer, io) = SocketIoBuilder::new().with_state(rooms).build_layer();
@@ -24,8 +31,8 @@ pub async fn axum() -> shuttle_axum::ShuttleAxum {
     // Serve the socket server at the root path using the on_connect handler
     io.ns("/", socket::on_connect);
 
-    // Make a axum router with the socket server layer and a CORS layer
-    let app = Router::new().layer(
+    // Make a axum router with the HTTP routes, the socket server layer and a CORS layer
+    let app = Router::new().merge(http::routes(accounts)).layer(
         ServiceBuilder::new()
             .layer(CorsLayer::permissive())
             .layer(layer),
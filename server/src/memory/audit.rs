@@ -0,0 +1,100 @@
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::chess::util::count_pieces;
+
+use super::board::{MemoryBoard, TileMismatch};
+
+// Outcome of comparing a memory board with the chess board it belongs to
+// removed lists the tiles taken off the board while reconciling
+#[derive(Debug, Clone, Serialize, PartialEq, Default)]
+pub struct AuditReport {
+    pub mismatches: Vec<TileMismatch>,
+    pub removed: Vec<usize>,
+}
+
+impl AuditReport {
+    pub fn is_consistent(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+pub fn audit(room_id: &str, memory: &MemoryBoard, board: &chess::Board) -> AuditReport {
+    // Compares tile counts per piece with the chess board and logs every mismatch
+    let mismatches = memory.mismatches(board);
+    for mismatch in &mismatches {
+        warn!(
+            "Memory board out of sync in room {}: {} tiles of {} for {} pieces",
+            room_id, mismatch.tiles, mismatch.tile, mismatch.pieces
+        );
+    }
+    AuditReport {
+        mismatches,
+        removed: vec![],
+    }
+}
+
+pub fn reconcile(room_id: &str, memory: &mut MemoryBoard, board: &chess::Board) -> AuditReport {
+    // Audits the board, then removes the orphaned pairs of pieces that left the chess board
+    let mut report = audit(room_id, memory, board);
+    for mismatch in &report.mismatches {
+        let (piece, color) = match mismatch.tile.piece() {
+            Some(p) => p,
+            None => continue,
+        };
+        let removed = memory.remove_surplus(mismatch.tile, count_pieces(board, piece, color));
+        if !removed.is_empty() {
+            info!(
+                "Removed orphaned {} tiles {:?} in room {}",
+                mismatch.tile, removed, room_id
+            );
+        }
+        report.removed.extend(removed);
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn without_black_rooks() -> chess::Board {
+        "1nbqkbn1/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQ - 0 1"
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn consistent_board_passes() {
+        let memory = MemoryBoard::new(0);
+        let report = audit("room", &memory, &chess::Board::default());
+        assert!(report.is_consistent());
+        assert!(report.removed.is_empty());
+    }
+
+    #[test]
+    fn audit_only_reports() {
+        let memory = MemoryBoard::new(0);
+        let report = audit("room", &memory, &without_black_rooks());
+        assert_eq!(
+            report.mismatches,
+            vec![TileMismatch {
+                tile: "br".parse().unwrap(),
+                tiles: 4,
+                pieces: 0,
+            }]
+        );
+        assert_eq!(memory.count("br".parse().unwrap()), 4);
+    }
+
+    #[test]
+    fn reconcile_removes_orphaned_pairs() {
+        let mut memory = MemoryBoard::new(0);
+        let board = without_black_rooks();
+        let report = reconcile("room", &mut memory, &board);
+        assert_eq!(report.mismatches.len(), 1);
+        assert_eq!(report.removed.len(), 4);
+        assert_eq!(memory.count("br".parse().unwrap()), 0);
+        assert!(audit("room", &memory, &board).is_consistent());
+    }
+}
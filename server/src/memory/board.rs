@@ -1,39 +1,62 @@
-use rand::{thread_rng, Rng};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chess::Color;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::chess::util::count_pieces;
+
+use super::tile::{Card, PieceKind, Tile};
 
 // Struct to hold the memory board
-// board is a vector of strings, each string represents a tile on the board
-// flips stores the last 2 flipped tiles, for matching or unfilpping
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// board holds the 64 tiles, flips stores the last 2 flipped tiles, for matching or unfilpping
+// rng is seeded once per game and drives every random choice, so a game can be replayed from its seed
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct MemoryBoard {
+    board: Vec<Tile>,
+    flips: Vec<usize>,
+    rng: ChaCha8Rng,
+}
+
+// What clients get to see of the memory board
+// Face-down tiles are sent as "?", only empty slots and flipped tiles keep their value
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct MemoryBoardView {
     board: Vec<String>,
     flips: Vec<usize>,
 }
 
+// A piece whose memory tiles don't add up with the chess board
+// Every piece has at most one pair of tiles, matched pairs can leave a piece without tiles
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TileMismatch {
+    pub tile: Card,
+    pub tiles: usize,
+    pub pieces: usize,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct MatchedTiles {
-    tile: String,
+    tile: Option<Card>,
     matches: Vec<usize>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Tile {
-    index: usize,
-    value: String,
-}
-
 impl MatchedTiles {
     pub fn new_empty() -> Self {
         Self {
-            tile: String::new(),
+            tile: None,
             matches: vec![],
         }
     }
-    pub fn new(tile: String, matches: Vec<usize>) -> Self {
-        Self { tile, matches }
+    pub fn new(tile: Card, matches: Vec<usize>) -> Self {
+        Self {
+            tile: Some(tile),
+            matches,
+        }
     }
-    pub fn get_tile(&self) -> &str {
-        &self.tile
+    pub fn get_tile(&self) -> Option<Card> {
+        self.tile
     }
     pub fn get_matches(&self) -> Vec<usize> {
         self.matches.clone()
@@ -41,52 +64,53 @@ impl MatchedTiles {
 }
 
 impl MemoryBoard {
-    pub fn new() -> Self {
-        // Creates a new board
-
-        // Available tiles, 60 of them represent chess pieces, 4 are for wildcards
-        // x is a wildcard, w is white, b is black
-        // q is queen, r is rook, b is bishop, n is knight, p is pawn
-        // _ at the end means the tile has been flipped
-        // matched tiles will become empty strings
-        const TILES: [&str; 64] = [
-            "x", "bq", "br", "br", "bb", "bb", "bn", "bn", "bp", "bp", "bp", "bp", "bp", "bp",
-            "bp", "bp", "x", "wq", "wr", "wr", "wb", "wb", "wn", "wn", "wp", "wp", "wp", "wp",
-            "wp", "wp", "wp", "wp", "x", "bq", "br", "br", "bb", "bb", "bn", "bn", "bp", "bp",
-            "bp", "bp", "bp", "bp", "bp", "bp", "x", "wq", "wr", "wr", "wb", "wb", "wn", "wn",
-            "wp", "wp", "wp", "wp", "wp", "wp", "wp", "wp",
-        ];
-
-        // Add tiles from the above array to the board
-        let mut game_board = TILES.clone().to_vec();
+    pub fn new(seed: u64) -> Self {
+        // Creates a new board shuffled from the given seed
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+        // Every piece except the king has a pair of cards, 60 in total, plus 4 wildcards
+        let mut game_board = vec![Tile::FaceDown(Card::Wildcard); 4];
+        for card in Card::all_pieces() {
+            let pieces = match card {
+                Card::Piece(_, PieceKind::Pawn) => 8,
+                Card::Piece(_, PieceKind::Queen) => 1,
+                _ => 2,
+            };
+            game_board.extend(vec![Tile::FaceDown(card); pieces * 2]);
+        }
+
         // Shuffle the board
         for i in 0..62 {
-            let j = thread_rng().gen_range((i + 1)..64);
+            let j = rng.gen_range((i + 1)..64);
             game_board.swap(i, j);
         }
 
         Self {
-            board: game_board.into_iter().map(|s| s.to_string()).collect(),
+            board: game_board,
             flips: vec![],
+            rng,
         }
     }
 
-    pub fn flip_tile(&mut self, index: usize) -> Option<String> {
+    pub fn flip_tile(&mut self, index: usize) -> Option<Card> {
         // Flips the tile at the given index
 
         // If 2 tiles are already flipped, do nothing
-        // If the tile is already flipped, do nothing
-        // If the tile is empty, do nothing
-        if self.flips.len() == 2 || self.flips.contains(&index) || self.board[index].is_empty() {
+        // If the tile is out of bounds, empty or already flipped, do nothing
+        if self.flips.len() == 2 {
             return None;
         }
+        let card = match self.board.get(index) {
+            Some(Tile::FaceDown(card)) => *card,
+            _ => return None,
+        };
 
         // Add the index to the flips
         self.flips.push(index);
-        self.board[index] += "_";
+        self.board[index] = Tile::FaceUp(card);
 
-        // Return the value of the flipped tile(without _)
-        Some(self.board[index].trim_matches('_').to_string())
+        // Return the card on the flipped tile
+        Some(card)
     }
 
     pub fn match_tiles(&mut self) -> MatchedTiles {
@@ -94,98 +118,85 @@ impl MemoryBoard {
         if self.flips.len() != 2 {
             return MatchedTiles::new_empty();
         }
-        let t1 = self.board[self.flips[0]].clone();
-        let t2 = self.board[self.flips[1]].clone();
-        let t1 = t1.trim_matches('_');
-        let t2 = t2.trim_matches('_');
-        let mut matches = vec![];
-        // Check for wildcards
-        if t1 == "x" || t2 == "x" {
-            // Wildcard will match any tile
-            // Check if both tiles are wildcards
-            if t1 == "x" && t2 == "x" {
-                // Clear both tiles
-                self.board[self.flips[0]] = String::new();
-                self.board[self.flips[1]] = String::new();
-                matches.push(self.flips[0]);
-                matches.push(self.flips[1]);
+        let (i1, i2) = (self.flips[0], self.flips[1]);
+        let (c1, c2) = match (self.board[i1].card(), self.board[i2].card()) {
+            (Some(c1), Some(c2)) => (c1, c2),
+            _ => {
                 self.flips.clear();
-                return MatchedTiles::new("x".to_string(), matches);
+                return MatchedTiles::new_empty();
+            }
+        };
+        self.flips.clear();
+
+        match (c1, c2) {
+            // Two wildcards clear each other
+            (Card::Wildcard, Card::Wildcard) => {
+                self.board[i1] = Tile::Empty;
+                self.board[i2] = Tile::Empty;
+                MatchedTiles::new(Card::Wildcard, vec![i1, i2])
+            }
+            // A wildcard clears the other tile along with that tile's actual match
+            (Card::Wildcard, card) | (card, Card::Wildcard) => {
+                let tile_index = if c1 == Card::Wildcard { i2 } else { i1 };
+                let mut matches = vec![i1, i2];
+                if let Some(matched_tile) = self.find_matching_tile(tile_index) {
+                    self.board[matched_tile] = Tile::Empty;
+                    matches.push(matched_tile);
+                }
+                self.board[i1] = Tile::Empty;
+                self.board[i2] = Tile::Empty;
+                MatchedTiles::new(card, matches)
+            }
+            // If the last 2 flipped tiles match, remove them
+            (c1, c2) if c1 == c2 => {
+                self.board[i1] = Tile::Empty;
+                self.board[i2] = Tile::Empty;
+                MatchedTiles::new(c1, vec![i1, i2])
+            }
+            // If the last 2 flipped tiles don't match, unflip the tiles
+            _ => {
+                self.board[i1] = Tile::FaceDown(c1);
+                self.board[i2] = Tile::FaceDown(c2);
+                MatchedTiles::new_empty()
             }
-            // Clear both tiles + the actual match of the non-wildcard tile
-            let tile_index = if t1 == "x" {
-                self.flips[1]
-            } else {
-                self.flips[0]
-            };
-            let tile = if t1 == "x" { t2 } else { t1 };
-            let matched_tile = self.find_matching_tile(tile_index).unwrap();
-            self.board[self.flips[0]] = String::new();
-            self.board[self.flips[1]] = String::new();
-            self.board[matched_tile] = String::new();
-            matches.push(self.flips[0]);
-            matches.push(self.flips[1]);
-            matches.push(matched_tile);
-            self.flips.clear();
-            return MatchedTiles::new(tile.to_string(), matches);
-        }
-
-        // Otherwise, check for equality
-        if t1 == t2 {
-            // If the last 2 flipped tiles match, return true and remove them
-            let tile = self.board[self.flips[0]].clone();
-            self.board[self.flips[0]] = String::new();
-            self.board[self.flips[1]] = String::new();
-            matches.push(self.flips[0]);
-            matches.push(self.flips[1]);
-            self.flips.clear();
-            let tile = tile.trim_matches('_').to_string();
-            MatchedTiles::new(tile, matches)
-        } else {
-            // If the last 2 flipped tiles don't match, return false and unflip the tiles
-            self.board[self.flips[0]].pop();
-            self.board[self.flips[1]].pop();
-            self.flips.clear();
-            MatchedTiles::new_empty()
         }
     }
-    fn find_matching_tile(&self, index: usize) -> Option<usize> {
+    fn find_matching_tile(&mut self, index: usize) -> Option<usize> {
         // Find the matching tile for the given index
-        let tile = self.board[index].trim_matches('_');
-        let mut matches = vec![];
-        for (i, t) in self.board.iter().enumerate() {
-            if i != index && t.trim_matches('_') == tile {
-                matches.push(i);
-            }
-        }
+        let card = self.board[index].card()?;
+        let matches: Vec<usize> = (0..self.board.len())
+            .filter(|i| *i != index && self.board[*i].holds(card))
+            .collect();
         // Return a random matching tile
         if matches.is_empty() {
             None
         } else {
-            let i = thread_rng().gen_range(0..matches.len());
+            let i = self.rng.gen_range(0..matches.len());
             Some(matches[i])
         }
     }
 
-    pub fn remove_tiles(&mut self, piece: String) -> Vec<usize> {
+    fn find_tiles(&self, card: Card) -> Vec<usize> {
+        // Returns the indices of every tile holding the given card
+        (0..self.board.len())
+            .filter(|i| self.board[*i].holds(card))
+            .collect()
+    }
+
+    pub fn remove_tiles(&mut self, card: Card) -> Vec<usize> {
         // Find matching tiles
-        let mut matches = vec![];
-        for (i, tile) in self.board.iter_mut().enumerate() {
-            if tile.trim_matches('_') == &piece {
-                matches.push(i);
-            }
-        }
+        let matches = self.find_tiles(card);
 
         let mut removed = vec![];
         // Remove 2 random matching tiles
         if matches.len() >= 2 {
-            let i = thread_rng().gen_range(0..matches.len());
-            self.board[matches[i]] = String::new();
+            let i = self.rng.gen_range(0..matches.len());
+            self.board[matches[i]] = Tile::Empty;
             let mut j = i;
             while j == i {
-                j = thread_rng().gen_range(0..matches.len());
+                j = self.rng.gen_range(0..matches.len());
             }
-            self.board[matches[j]] = String::new();
+            self.board[matches[j]] = Tile::Empty;
             removed.push(matches[i]);
             removed.push(matches[j]);
         }
@@ -193,50 +204,114 @@ impl MemoryBoard {
         removed
     }
 
+    pub fn count(&self, card: Card) -> usize {
+        // Returns how many tiles of the given card are left on the board
+        self.find_tiles(card).len()
+    }
+
+    pub fn remove_surplus(&mut self, card: Card, pieces: usize) -> Vec<usize> {
+        // Removes pairs of the given card until there are no more pairs than pieces
+        let mut removed = vec![];
+        while self.count(card) / 2 > pieces {
+            let pair = self.remove_tiles(card);
+            if pair.is_empty() {
+                break;
+            }
+            removed.extend(pair);
+        }
+        removed
+    }
+
+    pub fn mismatches(&self, board: &chess::Board) -> Vec<TileMismatch> {
+        // Compares the tiles of every piece with the pieces on the chess board
+        let mut mismatches = vec![];
+        for card in Card::all_pieces() {
+            let (piece, color) = card.piece().unwrap();
+            let tiles = self.count(card);
+            let pieces = count_pieces(board, piece, color);
+            if !tiles.is_multiple_of(2) || tiles / 2 > pieces {
+                mismatches.push(TileMismatch {
+                    tile: card,
+                    tiles,
+                    pieces,
+                });
+            }
+        }
+        mismatches
+    }
+
     pub fn get_flips(&self) -> Vec<usize> {
         // Returns the indices of the flipped tiles
         self.flips.clone()
     }
 
-    pub fn upgrade_tile(&mut self, piece: String, color: chess::Color) -> Option<(Tile, Tile)> {
-        // Upgrades the tile to the given piece
-        // Find the pawn tiles matching the given piece
-        let color = match color {
-            chess::Color::White => 'w',
-            chess::Color::Black => 'b',
-        };
-        let target = format!("{}p", color);
-        let mut matches = vec![];
-        for (i, tile) in self.board.iter().enumerate() {
-            if tile.trim_matches('_') == target {
-                matches.push(i);
-            }
-        }
+    pub fn upgrade_tile(&mut self, kind: PieceKind, color: Color) -> Option<(usize, usize)> {
+        // Turns a random pair of pawn tiles of the given color into the given piece
+        let matches = self.find_tiles(Card::Piece(color, PieceKind::Pawn));
         if matches.len() < 2 {
             return None;
         }
 
-        let t1 = rand::thread_rng().gen_range(0..matches.len());
+        let t1 = self.rng.gen_range(0..matches.len());
         let mut t2 = t1;
         while t2 == t1 {
-            t2 = rand::thread_rng().gen_range(0..matches.len());
+            t2 = self.rng.gen_range(0..matches.len());
         }
 
         // Set the matching tiles to the given piece
-        self.board[matches[t1]] = format!("{}{}", color, piece.to_ascii_lowercase());
-        self.board[matches[t2]] = format!("{}{}", color, piece.to_ascii_lowercase());
+        let card = Card::Piece(color, kind);
+        self.board[matches[t1]] = Tile::FaceDown(card);
+        self.board[matches[t2]] = Tile::FaceDown(card);
 
         // Return affected tile indices
-        Some((
-            Tile {
-                index: matches[t1],
-                value: self.board[matches[t1]].clone(),
-            },
-            Tile {
-                index: matches[t2],
-                value: self.board[matches[t2]].clone(),
-            },
-        ))
+        Some((matches[t1], matches[t2]))
+    }
+
+    pub fn redacted(&self) -> MemoryBoardView {
+        // Returns the board as clients are allowed to see it
+        let board = self
+            .board
+            .iter()
+            .map(|tile| match tile {
+                Tile::FaceDown(_) => "?".to_string(),
+                _ => tile.to_string(),
+            })
+            .collect();
+        MemoryBoardView {
+            board,
+            flips: self.flips.clone(),
+        }
+    }
+
+    pub fn fingerprint(&self, salt: u64) -> String {
+        // Returns a hash of the cards left on the board, whether they are face up or not
+        // Salted with the game's secret seed, so the layout can't be recovered by guessing
+        let layout = self
+            .board
+            .iter()
+            .map(|tile| tile.card().map(|c| c.to_string()).unwrap_or_default())
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut hasher = Sha256::new();
+        hasher.update(salt.to_le_bytes());
+        hasher.update(layout);
+        URL_SAFE_NO_PAD.encode(hasher.finalize())
+    }
+
+    pub fn next_seed(&mut self) -> u64 {
+        // Draws the seed of the next game from this game's rng
+        self.rng.gen()
+    }
+
+    pub fn unflip_pending(&mut self) -> Vec<usize> {
+        // Turns the flipped tiles that weren't matched back down and returns them
+        let flips = std::mem::take(&mut self.flips);
+        for i in &flips {
+            if let Tile::FaceUp(card) = self.board[*i] {
+                self.board[*i] = Tile::FaceDown(card);
+            }
+        }
+        flips
     }
 
     pub fn reset_flips(&mut self) {
@@ -244,3 +319,160 @@ impl MemoryBoard {
         self.flips.clear();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: u64 = 7;
+
+    fn card(code: &str) -> Card {
+        code.parse().unwrap()
+    }
+
+    fn flip_pair(board: &mut MemoryBoard, first: Card, second: Card) {
+        let i = board.find_tiles(first)[0];
+        let j = board
+            .find_tiles(second)
+            .into_iter()
+            .find(|j| *j != i)
+            .unwrap();
+        board.flip_tile(i).unwrap();
+        board.flip_tile(j).unwrap();
+    }
+
+    #[test]
+    fn new_board_matches_the_starting_position() {
+        let board = MemoryBoard::new(SEED);
+        assert!(board.mismatches(&chess::Board::default()).is_empty());
+        assert_eq!(board.count(card("wp")), 16);
+        assert_eq!(board.count(card("bq")), 2);
+        assert_eq!(board.count(card("x")), 4);
+    }
+
+    #[test]
+    fn surplus_pairs_are_reported_and_removed() {
+        let mut board = MemoryBoard::new(SEED);
+        // Black queen captured without its tiles being removed
+        let position: chess::Board = "rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            board.mismatches(&position),
+            vec![TileMismatch {
+                tile: card("bq"),
+                tiles: 2,
+                pieces: 0,
+            }]
+        );
+
+        assert_eq!(board.remove_surplus(card("bq"), 0).len(), 2);
+        assert_eq!(board.count(card("bq")), 0);
+        assert!(board.remove_surplus(card("wq"), 1).is_empty());
+    }
+
+    #[test]
+    fn pieces_may_outnumber_their_pairs() {
+        let mut board = MemoryBoard::new(SEED);
+        board.remove_tiles(card("wn"));
+        assert!(board.mismatches(&chess::Board::default()).is_empty());
+    }
+
+    #[test]
+    fn flips_ignore_bad_indices_and_face_up_tiles() {
+        let mut board = MemoryBoard::new(SEED);
+        assert_eq!(board.flip_tile(64), None);
+        assert!(board.flip_tile(3).is_some());
+        assert_eq!(board.flip_tile(3), None);
+        board.board[4] = Tile::Empty;
+        assert_eq!(board.flip_tile(4), None);
+        assert_eq!(board.get_flips(), vec![3]);
+    }
+
+    #[test]
+    fn matching_pair_is_removed() {
+        let mut board = MemoryBoard::new(SEED);
+        flip_pair(&mut board, card("br"), card("br"));
+        let matched = board.match_tiles();
+        assert_eq!(matched.get_tile(), Some(card("br")));
+        assert_eq!(matched.get_matches().len(), 2);
+        assert_eq!(board.count(card("br")), 2);
+        assert!(board.get_flips().is_empty());
+    }
+
+    #[test]
+    fn mismatched_tiles_are_turned_back_down() {
+        let mut board = MemoryBoard::new(SEED);
+        flip_pair(&mut board, card("br"), card("wr"));
+        let flips = board.get_flips();
+        assert_eq!(board.match_tiles(), MatchedTiles::new_empty());
+        for i in flips {
+            assert!(matches!(board.board[i], Tile::FaceDown(_)));
+        }
+        assert_eq!(board.count(card("br")), 4);
+        assert_eq!(board.count(card("wr")), 4);
+    }
+
+    #[test]
+    fn wildcard_takes_the_other_tiles_partner() {
+        let mut board = MemoryBoard::new(SEED);
+        flip_pair(&mut board, card("x"), card("bn"));
+        let matched = board.match_tiles();
+        assert_eq!(matched.get_tile(), Some(card("bn")));
+        assert_eq!(matched.get_matches().len(), 3);
+        assert_eq!(board.count(card("bn")), 2);
+        assert_eq!(board.count(card("x")), 3);
+    }
+
+    #[test]
+    fn upgrade_turns_a_pawn_pair_into_the_new_piece() {
+        let mut board = MemoryBoard::new(SEED);
+        let (i, j) = board.upgrade_tile(PieceKind::Knight, Color::Black).unwrap();
+        assert_eq!(board.board[i], Tile::FaceDown(card("bn")));
+        assert_eq!(board.board[j], Tile::FaceDown(card("bn")));
+        assert_eq!(board.count(card("bp")), 14);
+        assert_eq!(board.count(card("bn")), 6);
+    }
+
+    #[test]
+    fn redacted_board_hides_face_down_tiles() {
+        let board = MemoryBoard::new(SEED);
+        let view = board.redacted();
+        assert!(view.board.iter().all(|tile| tile == "?"));
+        assert!(view.flips.is_empty());
+    }
+
+    #[test]
+    fn redacted_board_shows_flipped_and_empty_tiles() {
+        let mut board = MemoryBoard::new(SEED);
+        let value = board.flip_tile(5).unwrap();
+        board.board[9] = Tile::Empty;
+
+        let view = board.redacted();
+        assert_eq!(view.board[5], format!("{}_", value));
+        assert_eq!(view.board[9], "");
+        assert_eq!(view.flips, vec![5]);
+        assert_eq!(view.board.iter().filter(|tile| *tile == "?").count(), 62);
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_game() {
+        let mut a = MemoryBoard::new(SEED);
+        let mut b = MemoryBoard::new(SEED);
+        assert_eq!(a.board, b.board);
+
+        // Random choices after the shuffle follow the seed as well
+        assert_eq!(a.remove_tiles(card("wp")), b.remove_tiles(card("wp")));
+        assert_eq!(
+            a.upgrade_tile(PieceKind::Queen, Color::Black),
+            b.upgrade_tile(PieceKind::Queen, Color::Black)
+        );
+        assert_eq!(a.next_seed(), b.next_seed());
+        assert_eq!(a.board, b.board);
+    }
+
+    #[test]
+    fn different_seeds_shuffle_differently() {
+        assert_ne!(MemoryBoard::new(1).board, MemoryBoard::new(2).board);
+    }
+}
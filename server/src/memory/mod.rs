@@ -1 +1,3 @@
+pub mod audit;
 pub mod board;
+pub mod tile;
@@ -0,0 +1,255 @@
+use std::{fmt, str::FromStr};
+
+use chess::{Color, Piece};
+use serde::{Deserialize, Serialize};
+
+// Pieces that have cards on the memory board, kings have none
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PieceKind {
+    Pawn,
+    Knight,
+    Bishop,
+    Rook,
+    Queen,
+}
+
+pub const ALL_PIECE_KINDS: [PieceKind; 5] = [
+    PieceKind::Pawn,
+    PieceKind::Knight,
+    PieceKind::Bishop,
+    PieceKind::Rook,
+    PieceKind::Queen,
+];
+
+// Face of a memory card, either a chess piece or a wildcard
+// On the wire a card is its code, e.g. "wq" for a white queen or "x" for a wildcard
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum Card {
+    Piece(Color, PieceKind),
+    Wildcard,
+}
+
+// Slot on the memory board
+// On the wire an empty slot is "", a face-down card is its code and a face-up card has a trailing "_"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum Tile {
+    Empty,
+    FaceDown(Card),
+    FaceUp(Card),
+}
+
+impl PieceKind {
+    fn to_char(self) -> char {
+        match self {
+            PieceKind::Pawn => 'p',
+            PieceKind::Knight => 'n',
+            PieceKind::Bishop => 'b',
+            PieceKind::Rook => 'r',
+            PieceKind::Queen => 'q',
+        }
+    }
+
+    fn from_char(c: char) -> Option<Self> {
+        match c {
+            'p' => Some(PieceKind::Pawn),
+            'n' => Some(PieceKind::Knight),
+            'b' => Some(PieceKind::Bishop),
+            'r' => Some(PieceKind::Rook),
+            'q' => Some(PieceKind::Queen),
+            _ => None,
+        }
+    }
+}
+
+impl From<PieceKind> for Piece {
+    fn from(kind: PieceKind) -> Self {
+        match kind {
+            PieceKind::Pawn => Piece::Pawn,
+            PieceKind::Knight => Piece::Knight,
+            PieceKind::Bishop => Piece::Bishop,
+            PieceKind::Rook => Piece::Rook,
+            PieceKind::Queen => Piece::Queen,
+        }
+    }
+}
+
+impl TryFrom<Piece> for PieceKind {
+    type Error = ();
+
+    fn try_from(piece: Piece) -> Result<Self, Self::Error> {
+        match piece {
+            Piece::Pawn => Ok(PieceKind::Pawn),
+            Piece::Knight => Ok(PieceKind::Knight),
+            Piece::Bishop => Ok(PieceKind::Bishop),
+            Piece::Rook => Ok(PieceKind::Rook),
+            Piece::Queen => Ok(PieceKind::Queen),
+            Piece::King => Err(()),
+        }
+    }
+}
+
+impl Card {
+    pub fn from_piece(piece: Piece, color: Color) -> Option<Self> {
+        // Returns the card of a chess piece, kings have no cards
+        PieceKind::try_from(piece)
+            .ok()
+            .map(|kind| Card::Piece(color, kind))
+    }
+
+    pub fn piece(self) -> Option<(Piece, Color)> {
+        // Returns the chess piece on the card, wildcards have none
+        match self {
+            Card::Piece(color, kind) => Some((kind.into(), color)),
+            Card::Wildcard => None,
+        }
+    }
+
+    pub fn all_pieces() -> impl Iterator<Item = Card> {
+        // Iterates over the card of every piece kind of both colors
+        [Color::White, Color::Black].into_iter().flat_map(|color| {
+            ALL_PIECE_KINDS
+                .into_iter()
+                .map(move |kind| Card::Piece(color, kind))
+        })
+    }
+}
+
+impl Tile {
+    pub fn card(self) -> Option<Card> {
+        // Returns the card in the slot whichever way it is facing
+        match self {
+            Tile::Empty => None,
+            Tile::FaceDown(card) | Tile::FaceUp(card) => Some(card),
+        }
+    }
+
+    pub fn holds(self, card: Card) -> bool {
+        // Returns true if the slot has the given card, face up or down
+        self.card() == Some(card)
+    }
+}
+
+impl fmt::Display for Card {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Card::Piece(Color::White, kind) => write!(f, "w{}", kind.to_char()),
+            Card::Piece(Color::Black, kind) => write!(f, "b{}", kind.to_char()),
+            Card::Wildcard => write!(f, "x"),
+        }
+    }
+}
+
+impl FromStr for Card {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        let card = match (chars.next(), chars.next()) {
+            (Some('x'), None) => Some(Card::Wildcard),
+            (Some('w'), Some(c)) => PieceKind::from_char(c).map(|k| Card::Piece(Color::White, k)),
+            (Some('b'), Some(c)) => PieceKind::from_char(c).map(|k| Card::Piece(Color::Black, k)),
+            _ => None,
+        };
+        match (card, chars.next()) {
+            (Some(card), None) => Ok(card),
+            _ => Err(format!("Invalid card {:?}", s)),
+        }
+    }
+}
+
+impl From<Card> for String {
+    fn from(card: Card) -> Self {
+        card.to_string()
+    }
+}
+
+impl TryFrom<String> for Card {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Tile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Tile::Empty => Ok(()),
+            Tile::FaceDown(card) => write!(f, "{}", card),
+            Tile::FaceUp(card) => write!(f, "{}_", card),
+        }
+    }
+}
+
+impl From<Tile> for String {
+    fn from(tile: Tile) -> Self {
+        tile.to_string()
+    }
+}
+
+impl TryFrom<String> for Tile {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        if s.is_empty() {
+            return Ok(Tile::Empty);
+        }
+        match s.strip_suffix('_') {
+            Some(card) => Ok(Tile::FaceUp(card.parse()?)),
+            None => Ok(Tile::FaceDown(s.parse()?)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cards_round_trip_through_their_codes() {
+        for card in Card::all_pieces().chain([Card::Wildcard]) {
+            assert_eq!(card.to_string().parse::<Card>(), Ok(card));
+        }
+        assert_eq!(
+            "wq".parse::<Card>(),
+            Ok(Card::Piece(Color::White, PieceKind::Queen))
+        );
+        for code in ["", "wk", "bk", "w", "wqq", "zq", "WQ", "x_"] {
+            assert!(code.parse::<Card>().is_err(), "{} parsed", code);
+        }
+    }
+
+    #[test]
+    fn tiles_keep_the_string_wire_format() {
+        let queen = Card::Piece(Color::Black, PieceKind::Queen);
+        let tiles = vec![
+            Tile::Empty,
+            Tile::FaceDown(queen),
+            Tile::FaceUp(Card::Wildcard),
+        ];
+        let json = serde_json::to_string(&tiles).unwrap();
+        assert_eq!(json, r#"["","bq","x_"]"#);
+        assert_eq!(serde_json::from_str::<Vec<Tile>>(&json).unwrap(), tiles);
+        assert!(serde_json::from_str::<Tile>(r#""bk""#).is_err());
+    }
+
+    #[test]
+    fn kings_have_no_cards() {
+        assert_eq!(Card::from_piece(Piece::King, Color::White), None);
+        assert_eq!(
+            Card::from_piece(Piece::Rook, Color::White),
+            Some(Card::Piece(Color::White, PieceKind::Rook))
+        );
+        assert_eq!(Card::Wildcard.piece(), None);
+    }
+
+    #[test]
+    fn face_does_not_change_the_card() {
+        let knight = Card::Piece(Color::White, PieceKind::Knight);
+        assert!(Tile::FaceUp(knight).holds(knight));
+        assert!(Tile::FaceDown(knight).holds(knight));
+        assert!(!Tile::Empty.holds(knight));
+    }
+}
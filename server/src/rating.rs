@@ -0,0 +1,200 @@
+use serde::{Deserialize, Serialize};
+
+use crate::room::RoomType;
+
+// Glicko-2 system constants, see http://www.glicko.net/glicko/glicko2.pdf
+const SCALE: f64 = 173.7178; // converts between the Glicko and Glicko-2 scales
+const TAU: f64 = 0.5; // how much the volatility can change in one rating period
+const EPSILON: f64 = 0.000001; // convergence tolerance of the volatility iteration
+
+// Ratings are kept per pool, so casual play doesn't move timed ratings and vice versa
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum RatingPool {
+    Casual,
+    Timed,
+}
+
+// A Glicko-2 rating, on the Glicko scale players are used to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct Rating {
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+}
+
+// How a rated game moved a player's rating, rounded for display
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct RatingDelta {
+    pub rating: i32,
+    pub change: i32,
+}
+
+// Rating changes of the last rated game in a room, per player slot like the match score
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq)]
+pub struct RatingChange {
+    pub p1: Option<RatingDelta>,
+    pub p2: Option<RatingDelta>,
+}
+
+impl RatingPool {
+    pub fn of(room_type: &RoomType) -> Self {
+        match room_type {
+            RoomType::Casual => RatingPool::Casual,
+            RoomType::Timed(_) => RatingPool::Timed,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RatingPool::Casual => "casual",
+            RatingPool::Timed => "timed",
+        }
+    }
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        // Every player starts unrated, i.e. average with a large deviation
+        Self {
+            rating: 1500.0,
+            deviation: 350.0,
+            volatility: 0.06,
+        }
+    }
+}
+
+impl Rating {
+    pub fn update(&self, games: &[(Rating, f64)]) -> Rating {
+        // Returns the rating after a rating period with the given games and scores
+        // A score is 1 for a win, 0.5 for a draw and 0 for a loss
+        let mu = (self.rating - 1500.0) / SCALE;
+        let phi = self.deviation / SCALE;
+        if games.is_empty() {
+            // Only the deviation grows when a player doesn't play
+            let phi = (phi.powi(2) + self.volatility.powi(2)).sqrt();
+            return Rating {
+                deviation: phi * SCALE,
+                ..*self
+            };
+        }
+
+        // Estimated variance of the rating from the game outcomes, and the improvement
+        let mut v_inv = 0.0;
+        let mut improvement = 0.0;
+        for (opponent, score) in games {
+            let mu_j = (opponent.rating - 1500.0) / SCALE;
+            let g = g(opponent.deviation / SCALE);
+            let e = 1.0 / (1.0 + (-g * (mu - mu_j)).exp());
+            v_inv += g.powi(2) * e * (1.0 - e);
+            improvement += g * (score - e);
+        }
+        let v = 1.0 / v_inv;
+        let delta = v * improvement;
+
+        let volatility = self.new_volatility(phi, v, delta);
+        let phi_star = (phi.powi(2) + volatility.powi(2)).sqrt();
+        let phi = 1.0 / (1.0 / phi_star.powi(2) + 1.0 / v).sqrt();
+        let mu = mu + phi.powi(2) * improvement;
+        Rating {
+            rating: mu * SCALE + 1500.0,
+            deviation: phi * SCALE,
+            volatility,
+        }
+    }
+
+    fn new_volatility(&self, phi: f64, v: f64, delta: f64) -> f64 {
+        // Solves for the new volatility with the Illinois algorithm
+        let a = self.volatility.powi(2).ln();
+        let f = |x: f64| {
+            let ex = x.exp();
+            ex * (delta.powi(2) - phi.powi(2) - v - ex) / (2.0 * (phi.powi(2) + v + ex).powi(2))
+                - (x - a) / TAU.powi(2)
+        };
+
+        let mut lower = a;
+        let mut upper = if delta.powi(2) > phi.powi(2) + v {
+            (delta.powi(2) - phi.powi(2) - v).ln()
+        } else {
+            let mut k = 1.0;
+            while f(a - k * TAU) < 0.0 {
+                k += 1.0;
+            }
+            a - k * TAU
+        };
+        let (mut f_lower, mut f_upper) = (f(lower), f(upper));
+        while (upper - lower).abs() > EPSILON {
+            let c = lower + (lower - upper) * f_lower / (f_upper - f_lower);
+            let f_c = f(c);
+            if f_c * f_upper <= 0.0 {
+                lower = upper;
+                f_lower = f_upper;
+            } else {
+                f_lower /= 2.0;
+            }
+            upper = c;
+            f_upper = f_c;
+        }
+        (lower / 2.0).exp()
+    }
+
+    pub fn delta_from(&self, before: &Rating) -> RatingDelta {
+        RatingDelta {
+            rating: self.rating.round() as i32,
+            change: (self.rating - before.rating).round() as i32,
+        }
+    }
+}
+
+fn g(phi: f64) -> f64 {
+    // Weighs a game by how certain the opponent's rating is
+    1.0 / (1.0 + 3.0 * phi.powi(2) / std::f64::consts::PI.powi(2)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rating(rating: f64, deviation: f64) -> Rating {
+        Rating {
+            rating,
+            deviation,
+            volatility: 0.06,
+        }
+    }
+
+    #[test]
+    fn matches_the_worked_example() {
+        // The example from Glickman's paper
+        let player = rating(1500.0, 200.0);
+        let games = [
+            (rating(1400.0, 30.0), 1.0),
+            (rating(1550.0, 100.0), 0.0),
+            (rating(1700.0, 300.0), 0.0),
+        ];
+        let new = player.update(&games);
+        assert!((new.rating - 1464.06).abs() < 0.01);
+        assert!((new.deviation - 151.52).abs() < 0.01);
+        assert!((new.volatility - 0.05999).abs() < 0.00001);
+    }
+
+    #[test]
+    fn winners_gain_what_losers_lose() {
+        let (a, b) = (Rating::default(), Rating::default());
+        let (new_a, new_b) = (a.update(&[(b, 1.0)]), b.update(&[(a, 0.0)]));
+        assert!(new_a.rating > 1500.0);
+        assert_eq!(new_a.delta_from(&a).change, -new_b.delta_from(&b).change);
+
+        let draw = a.update(&[(b, 0.5)]);
+        assert_eq!(draw.delta_from(&a).change, 0);
+        assert!(draw.deviation < a.deviation);
+    }
+
+    #[test]
+    fn idle_players_grow_less_certain() {
+        let player = rating(1500.0, 50.0);
+        let idle = player.update(&[]);
+        assert_eq!(idle.rating, 1500.0);
+        assert!(idle.deviation > 50.0);
+    }
+}
@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+
+// What happens to a game when a player disconnects in the middle of it
+// pause_clocks stops both clocks while the player is away, otherwise the clock of the player to
+// move keeps running. After grace_period seconds the player who stayed wins, None waits forever
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct AbandonPolicy {
+    pub pause_clocks: bool,
+    pub grace_period: Option<u64>,
+}
+
+// What a player leaving did to the game in their room
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Departure {
+    NoGame,
+    Aborted,
+    Suspended,
+}
+
+impl Default for AbandonPolicy {
+    fn default() -> Self {
+        Self {
+            pause_clocks: true,
+            grace_period: Some(60),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_fields_fall_back_to_the_defaults() {
+        let policy: AbandonPolicy = serde_json::from_str(r#"{"grace_period": 30}"#).unwrap();
+        assert!(policy.pause_clocks);
+        assert_eq!(policy.grace_period, Some(30));
+
+        let policy: AbandonPolicy = serde_json::from_str(r#"{"grace_period": null}"#).unwrap();
+        assert_eq!(policy.grace_period, None);
+    }
+}
@@ -0,0 +1,35 @@
+use serde::Serialize;
+
+// Game actions a player can send to the server during their turn
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GameAction {
+    MovePiece,
+    FlipTile,
+    MatchTiles,
+    MatchPiece,
+    ClearSquare,
+}
+
+// Reasons for the server refusing a game action
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectReason {
+    NotInRoom,
+    GameInactive,
+    NotYourTurn,
+    WrongPhase,
+}
+
+// Payload of the action_rejected event
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ActionRejected {
+    pub action: GameAction,
+    pub reason: RejectReason,
+}
+
+impl ActionRejected {
+    pub fn new(action: GameAction, reason: RejectReason) -> Self {
+        Self { action, reason }
+    }
+}
@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+// Who plays white, decided as soon as the second player joins
+// Random is drawn from a hash of the game's seed, so a replayed game gets the same colors
+// without the colors giving away anything about the seed
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorAssignment {
+    CreatorWhite,
+    CreatorBlack,
+    #[default]
+    Random,
+}
+
+impl ColorAssignment {
+    pub fn creator_is_white(self, seed: u64) -> bool {
+        // Returns true if the room's creator plays white in the game with the given seed
+        match self {
+            ColorAssignment::CreatorWhite => true,
+            ColorAssignment::CreatorBlack => false,
+            ColorAssignment::Random => {
+                let hash = Sha256::new()
+                    .chain_update(b"colors")
+                    .chain_update(seed.to_le_bytes())
+                    .finalize();
+                hash[0] & 1 == 0
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn random_colors_follow_the_seed() {
+        let random = |seed| ColorAssignment::Random.creator_is_white(seed);
+        assert_eq!(random(42), random(42));
+        // Both colors come up, and not by the seed's parity
+        let whites = (0..64).filter(|&seed| random(seed)).count();
+        assert!(whites > 0 && whites < 64);
+        assert!((0..64).any(|seed| random(seed) != seed.is_multiple_of(2)));
+        assert!(ColorAssignment::CreatorWhite.creator_is_white(43));
+        assert!(!ColorAssignment::CreatorBlack.creator_is_white(42));
+
+        let parsed: ColorAssignment = serde_json::from_str(r#""creator_black""#).unwrap();
+        assert_eq!(parsed, ColorAssignment::CreatorBlack);
+    }
+}
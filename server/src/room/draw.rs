@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+// Plies that have to pass between two draw offers by the same player, i.e. 3 of their own moves
+pub const DRAW_OFFER_COOLDOWN: u32 = 6;
+
+// Draw offer bookkeeping of a game
+// Players are identified by their chess color, which survives reconnects
+// pending holds the color of the player whose offer is open, last_offer the ply of each
+// player's latest offer
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct DrawOffers {
+    pending: Option<String>,
+    last_offer: HashMap<String, u32>,
+}
+
+// Reasons for refusing a draw event
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DrawError {
+    NotInRoom,
+    GameInactive,
+    AlreadyPending,
+    TooSoon,
+    NoOffer,
+    OwnOffer,
+}
+
+impl DrawOffers {
+    pub fn offer(&mut self, color: &str, ply: u32) -> Result<(), DrawError> {
+        // Opens a draw offer, only one can be open and repeat offers have to wait
+        if self.pending.is_some() {
+            return Err(DrawError::AlreadyPending);
+        }
+        if let Some(last) = self.last_offer.get(color) {
+            if ply < last + DRAW_OFFER_COOLDOWN {
+                return Err(DrawError::TooSoon);
+            }
+        }
+        self.pending = Some(color.to_string());
+        self.last_offer.insert(color.to_string(), ply);
+        Ok(())
+    }
+
+    pub fn answer(&mut self, color: &str) -> Result<(), DrawError> {
+        // Closes the open offer on behalf of the player it was made to
+        match &self.pending {
+            None => Err(DrawError::NoOffer),
+            Some(offerer) if offerer == color => Err(DrawError::OwnOffer),
+            Some(_) => {
+                self.pending = None;
+                Ok(())
+            }
+        }
+    }
+
+    pub fn expire(&mut self, mover: &str) -> bool {
+        // Withdraws the open offer if its player just moved, returns true if it did
+        if self.pending.as_deref() == Some(mover) {
+            self.pending = None;
+            return true;
+        }
+        false
+    }
+
+    pub fn get_pending(&self) -> Option<String> {
+        // Returns the color of the player whose offer is open
+        self.pending.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_one_offer_at_a_time() {
+        let mut offers = DrawOffers::default();
+        assert_eq!(offers.offer("white", 0), Ok(()));
+        assert_eq!(offers.offer("black", 1), Err(DrawError::AlreadyPending));
+        assert_eq!(offers.answer("white"), Err(DrawError::OwnOffer));
+        assert_eq!(offers.answer("black"), Ok(()));
+        assert_eq!(offers.answer("black"), Err(DrawError::NoOffer));
+    }
+
+    #[test]
+    fn offer_expires_when_its_player_moves() {
+        let mut offers = DrawOffers::default();
+        offers.offer("white", 0).unwrap();
+        assert!(!offers.expire("black"));
+        assert!(offers.expire("white"));
+        assert_eq!(offers.get_pending(), None);
+    }
+
+    #[test]
+    fn repeat_offers_are_rate_limited() {
+        let mut offers = DrawOffers::default();
+        offers.offer("white", 2).unwrap();
+        offers.answer("black").unwrap();
+        assert_eq!(
+            offers.offer("white", 2 + DRAW_OFFER_COOLDOWN - 1),
+            Err(DrawError::TooSoon)
+        );
+        // The other player has their own limit
+        assert_eq!(offers.offer("black", 3), Ok(()));
+        offers.answer("white").unwrap();
+        assert_eq!(offers.offer("white", 2 + DRAW_OFFER_COOLDOWN), Ok(()));
+    }
+}
@@ -0,0 +1,126 @@
+use serde::{Deserialize, Serialize};
+
+// Plies without a pawn move, capture or memory removal after which the game is drawn
+pub const FIFTY_MOVE_PLIES: u32 = 100;
+
+// A position as far as repetition goes
+// chess holds the first four FEN fields (placement, side to move, castling and en passant),
+// memory a fingerprint of the memory board's layout, since removing tiles changes the game as much
+// as a capture, the layout itself would give away the face-down tiles
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Position {
+    pub chess: String,
+    pub memory: String,
+}
+
+// Positions since the last irreversible move, used for threefold repetition and the fifty-move rule
+// Pawn moves, captures and memory removals can't be undone, so no earlier position can come back
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct PositionHistory {
+    positions: Vec<Position>,
+    halfmove_clock: u32,
+}
+
+impl Position {
+    pub fn new(board: &chess::Board, memory: String) -> Self {
+        let fen = board.to_string();
+        let chess = fen.split(' ').take(4).collect::<Vec<_>>().join(" ");
+        Self { chess, memory }
+    }
+}
+
+impl PositionHistory {
+    pub fn new(start: Position) -> Self {
+        Self {
+            positions: vec![start],
+            halfmove_clock: 0,
+        }
+    }
+
+    pub fn record(&mut self, position: Position, irreversible: bool) {
+        // Adds the position reached by a move
+        // A changed memory board means tiles were removed, which is irreversible as well
+        let memory_changed = self
+            .positions
+            .last()
+            .is_some_and(|last| last.memory != position.memory);
+        if irreversible || memory_changed {
+            self.positions.clear();
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        self.positions.push(position);
+    }
+
+    pub fn is_threefold(&self) -> bool {
+        // Returns true if the current position has occurred three times
+        match self.positions.last() {
+            Some(current) => self.positions.iter().filter(|p| *p == current).count() >= 3,
+            None => false,
+        }
+    }
+
+    pub fn is_fifty_moves(&self) -> bool {
+        // Returns true if fifty moves each went by without an irreversible one
+        self.halfmove_clock >= FIFTY_MOVE_PLIES
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(chess: &str, memory: &str) -> Position {
+        Position {
+            chess: chess.to_string(),
+            memory: memory.to_string(),
+        }
+    }
+
+    #[test]
+    fn positions_ignore_the_move_counters() {
+        let board = chess::Board::default();
+        let p = Position::new(&board, String::new());
+        assert_eq!(
+            p.chess,
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -"
+        );
+    }
+
+    #[test]
+    fn third_occurrence_is_a_repetition() {
+        let mut history = PositionHistory::new(position("a", "m"));
+        for chess in ["b", "a", "b"] {
+            history.record(position(chess, "m"), false);
+            assert!(!history.is_threefold());
+        }
+        history.record(position("a", "m"), false);
+        assert!(history.is_threefold());
+    }
+
+    #[test]
+    fn memory_removals_reset_the_history() {
+        let mut history = PositionHistory::new(position("a", "m"));
+        history.record(position("b", "m"), false);
+        history.record(position("a", "m"), false);
+        history.record(position("b", "m2"), false);
+        history.record(position("a", "m2"), false);
+        history.record(position("b", "m2"), false);
+        assert!(!history.is_threefold());
+    }
+
+    #[test]
+    fn fifty_moves_without_progress_draw() {
+        let mut history = PositionHistory::new(position("0", "m"));
+        for i in 1..FIFTY_MOVE_PLIES {
+            history.record(position(&i.to_string(), "m"), false);
+        }
+        assert!(!history.is_fifty_moves());
+        history.record(position("pawn", "m"), true);
+        for i in 0..FIFTY_MOVE_PLIES {
+            history.record(position(&i.to_string(), "m"), false);
+        }
+        assert!(history.is_fifty_moves());
+    }
+}
@@ -0,0 +1,2173 @@
+use std::{
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use chess::{ChessMove, Color, Piece};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+
+use crate::{
+    chess::util::{captured_piece, count_pieces, GameResult},
+    clock::TimeControl,
+    memory::{
+        audit::{self, AuditReport},
+        board::MemoryBoard,
+        tile::{Card, PieceKind},
+    },
+    rating::{RatingChange, RatingDelta, RatingPool},
+    user::User,
+};
+
+use self::{
+    abandon::{AbandonPolicy, Departure},
+    action::{ActionRejected, GameAction, RejectReason},
+    color::ColorAssignment,
+    draw::{DrawError, DrawOffers},
+    history::{Position, PositionHistory},
+    phase::{PhaseEvent, TurnPhase},
+    rematch::{Consent, ConsentError, MatchScore},
+    removal::{RemovalEntitlement, RemovalError},
+    takeback::{TakebackError, Takebacks, TurnSnapshot},
+};
+
+pub mod abandon;
+pub mod action;
+pub mod color;
+pub mod draw;
+pub mod history;
+pub mod phase;
+pub mod rematch;
+pub mod removal;
+pub mod takeback;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum RoomState {
+    Waiting,
+    Ready,
+    Playing,
+    Suspended,
+    Over,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum RoomType {
+    Casual,
+    Timed(TimeControl),
+}
+
+impl std::fmt::Display for RoomType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RoomType::Casual => write!(f, "casual"),
+            RoomType::Timed(control) => write!(f, "timed {}", control),
+        }
+    }
+}
+
+// What a chess move did besides moving a piece
+#[derive(Debug, Clone, PartialEq)]
+pub struct MoveEffects {
+    pub captured: Option<(Piece, Color)>,
+    pub removed_tiles: Vec<usize>,
+}
+
+// Reasons for refusing a chess move, checked before the move touches the room
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MoveError {
+    IllegalFen,
+    IllegalMove,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Room {
+    // Stores all the information about a room
+    p1: Option<User>,
+    p2: Option<User>,
+    chess_fen: String,
+    seed: u64,
+    memory_board: MemoryBoard,
+    turn: String,
+    turn_count: u32,
+    plies: u32, // chess moves played this game
+    draw_offers: DrawOffers,
+    history: PositionHistory,
+    takebacks: Takebacks,
+    phase: TurnPhase,
+    removal: Option<RemovalEntitlement>,
+    state: RoomState,
+    room_type: RoomType,
+    memory_time: Option<u64>, // seconds per turn for the memory phase, if limited
+    abandon_policy: AbandonPolicy,
+    color_assignment: ColorAssignment,
+    creator_white: Option<bool>, // colors of the current game, alternating from the first one
+    score: MatchScore,
+    consent: Consent,
+    rated: bool, // whether finished games move the players' ratings
+    rating_change: RatingChange,
+    #[serde(skip)]
+    clock: Arc<Mutex<Option<JoinHandle<()>>>>, // task that watches for flag fall
+    #[serde(skip)]
+    memory_timer: Arc<Mutex<Option<JoinHandle<()>>>>, // task that ends the memory phase
+    #[serde(skip)]
+    abandon_timer: Arc<Mutex<Option<JoinHandle<()>>>>, // task that awards an abandoned game
+}
+
+impl Room {
+    pub fn new(
+        id: String,
+        name: String,
+        avatar: String,
+        avatar_orientation: u8,
+        avatar_color: String,
+        room_type: RoomType,
+        seed: u64,
+    ) -> Self {
+        let time = if let RoomType::Timed(control) = room_type {
+            control
+        } else {
+            TimeControl::default()
+        };
+        let p1 = User::new(
+            id,
+            name,
+            avatar,
+            avatar_orientation,
+            avatar_color,
+            String::new(),
+            time,
+        );
+
+        // Create a new room with the given id as player 1
+        Self {
+            p1: Some(p1),
+            p2: None,
+            chess_fen: chess::Board::default().to_string(),
+            seed,
+            memory_board: MemoryBoard::new(seed),
+            turn: String::new(),
+            turn_count: 0,
+            plies: 0,
+            draw_offers: DrawOffers::default(),
+            history: PositionHistory::default(),
+            takebacks: Takebacks::default(),
+            phase: TurnPhase::default(),
+            removal: None,
+            state: RoomState::Waiting,
+            room_type,
+            memory_time: None,
+            abandon_policy: AbandonPolicy::default(),
+            color_assignment: ColorAssignment::default(),
+            creator_white: None,
+            score: MatchScore::default(),
+            consent: Consent::default(),
+            rated: false,
+            rating_change: RatingChange::default(),
+            clock: Arc::new(Mutex::new(None)),
+            memory_timer: Arc::new(Mutex::new(None)),
+            abandon_timer: Arc::new(Mutex::new(None)),
+        }
+    }
+    pub fn connect_player(
+        &mut self,
+        p: String,
+        name: String,
+        avatar: String,
+        avatar_orientation: u8,
+        avatar_color: String,
+        seat: Option<String>,
+    ) -> Option<String> {
+        // Seats player p and returns their seat, None if the room has no seat for them
+        // A valid token takes its holder back to their own seat, newcomers get a free seat or
+        // the seat of a player whose token has run out
+        let time = self.get_time_control();
+        let holds = |user: &Option<User>| {
+            user.as_ref()
+                .zip(seat.as_deref())
+                .is_some_and(|(u, seat)| u.holds_seat(seat))
+        };
+        let expired = |user: &Option<User>| user.as_ref().is_some_and(|u| u.seat_expired());
+
+        let (slot, reconnecting) = if holds(&self.p1) {
+            (&mut self.p1, true)
+        } else if holds(&self.p2) {
+            (&mut self.p2, true)
+        } else if self.p2.is_none() || expired(&self.p2) {
+            (&mut self.p2, false)
+        } else if expired(&self.p1) {
+            (&mut self.p1, false)
+        } else {
+            return None;
+        };
+
+        let old_id = slot.as_ref().map(|u| u.get_id()).unwrap_or_default();
+        if reconnecting {
+            slot.as_mut().unwrap().reconnect(p.clone());
+        } else {
+            // A new opponent starts a new match, in the colors of the player they replace
+            if slot.is_some() {
+                self.score = MatchScore::default();
+                self.creator_white = None;
+            }
+            let chess_color = slot.as_ref().map(|u| u.get_chess_color());
+            *slot = Some(User::new(
+                p.clone(),
+                name,
+                avatar,
+                avatar_orientation,
+                avatar_color,
+                chess_color.unwrap_or_default(),
+                time,
+            ));
+        }
+        let seat = slot.as_ref().map(|u| u.get_seat());
+
+        // The turn follows the player to their new id, even if they are back alone
+        if !old_id.is_empty() && self.turn == old_id {
+            self.turn = p;
+        }
+
+        // Set room state based on the number of connected players
+        if self.player_count() == 1 {
+            return seat;
+        }
+
+        // Both players are back, so an abandoned game is no longer up for grabs
+        if let Some(handle) = self.abandon_timer.lock().unwrap().take() {
+            handle.abort();
+        }
+
+        if self.turn_count == 0 {
+            self.state = RoomState::Ready;
+            self.assign_colors();
+        } else {
+            self.state = RoomState::Playing;
+        }
+        seat
+    }
+    pub fn assign_colors(&mut self) -> Option<String> {
+        // Gives out the colors of the next game and hands white the first turn
+        // Returns the id of the player with white
+        let (Some(p1), Some(p2)) = (self.p1.as_mut(), self.p2.as_mut()) else {
+            return None;
+        };
+        let creator_white = *self
+            .creator_white
+            .get_or_insert_with(|| self.color_assignment.creator_is_white(self.seed));
+        let (white, black) = if creator_white { (p1, p2) } else { (p2, p1) };
+        white.set_chess_color("white".to_string());
+        black.set_chess_color("black".to_string());
+        self.turn = white.get_id();
+        Some(self.turn.clone())
+    }
+    pub fn set_color_assignment(&mut self, assignment: ColorAssignment) {
+        // Sets who plays white, from the next time colors are given out
+        self.color_assignment = assignment;
+        self.creator_white = None;
+    }
+    pub async fn start_game(&mut self) {
+        // Starts the game with white to move, colors were given out when the room became ready
+        self.state = RoomState::Playing;
+        if self.p1.is_none() || self.p2.is_none() {
+            return;
+        }
+        if let Some(white) = self.get_white() {
+            self.turn = white.get_id();
+            white.start_turn().await;
+        }
+        self.phase = TurnPhase::default();
+        self.removal = None;
+        self.history = PositionHistory::new(self.position());
+        self.takebacks = Takebacks::default();
+        let snapshot = self.snapshot().await;
+        self.takebacks.push(snapshot);
+        self.rating_change = RatingChange::default();
+    }
+    pub async fn disconnect_player(&mut self, p: String) -> Departure {
+        // Remove player p from the room, ids of nobody seated here change nothing
+        let user = [&mut self.p1, &mut self.p2]
+            .into_iter()
+            .flatten()
+            .find(|u| u.get_id() == p);
+        match user {
+            Some(user) => user.disconnect(),
+            None => return Departure::NoGame,
+        }
+
+        // A game nobody has moved in yet is aborted, any other game waits for the player
+        match self.state {
+            RoomState::Playing if !self.has_moves() => {
+                self.reset_game().await;
+                self.state = RoomState::Waiting;
+                Departure::Aborted
+            }
+            RoomState::Playing => {
+                self.state = RoomState::Suspended;
+                if self.abandon_policy.pause_clocks {
+                    self.stop_clocks().await;
+                } else {
+                    self.stop_memory_timer();
+                }
+                Departure::Suspended
+            }
+            RoomState::Suspended => Departure::NoGame,
+            _ => {
+                self.state = RoomState::Waiting;
+                Departure::NoGame
+            }
+        }
+    }
+    pub fn has_moves(&self) -> bool {
+        // Returns true if anything was played this game, a tile flip or a finished turn
+        // The board itself can't tell, pieces can move back and takebacks restore it
+        self.turn_count > 0
+    }
+    pub async fn save_clocks(&mut self) {
+        // Records the time left of both players, ahead of saving the room
+        for user in [&mut self.p1, &mut self.p2].into_iter().flatten() {
+            user.save_time().await;
+        }
+    }
+    pub async fn restore(&mut self) {
+        // Picks up a room loaded from a snapshot after a restart, with both players gone
+        // A game in progress waits for them like a suspended one, unless nobody had moved yet
+        let time = self.get_time_control();
+        for user in [&mut self.p1, &mut self.p2].into_iter().flatten() {
+            user.restore(time).await;
+        }
+        self.state = match self.state {
+            RoomState::Playing | RoomState::Suspended if !self.has_moves() => {
+                self.reset_game().await;
+                RoomState::Waiting
+            }
+            RoomState::Playing | RoomState::Suspended => RoomState::Suspended,
+            RoomState::Over => RoomState::Over,
+            RoomState::Waiting | RoomState::Ready => RoomState::Waiting,
+        };
+    }
+    pub fn is_abandoned(&self) -> bool {
+        // Returns true if nobody is left in the room and no token holds a seat anymore
+        [&self.p1, &self.p2]
+            .into_iter()
+            .flatten()
+            .all(|u| u.seat_expired())
+    }
+    pub async fn resume_clocks(&self) {
+        // Restarts the clock of the player to move after a pause
+        if self.state != RoomState::Playing {
+            return;
+        }
+        let user = [&self.p1, &self.p2]
+            .into_iter()
+            .flatten()
+            .find(|u| u.get_id() == self.turn);
+        if let Some(user) = user {
+            user.start_turn().await;
+        }
+    }
+    pub fn set_abandon_policy(&mut self, policy: AbandonPolicy) {
+        // Sets what happens when a player disconnects during a game
+        self.abandon_policy = policy;
+    }
+    pub fn get_abandon_policy(&self) -> AbandonPolicy {
+        // Returns what happens when a player disconnects during a game
+        self.abandon_policy
+    }
+    pub fn set_abandon_timer(&self, handle: JoinHandle<()>) {
+        // Replaces the abandonment timer, aborting the previous one
+        if let Some(old) = self.abandon_timer.lock().unwrap().replace(handle) {
+            old.abort();
+        }
+    }
+    pub async fn abandon(&mut self, p: &str) -> Option<GameResult> {
+        // Awards the game to the other player if player p is still away from a suspended game
+        if self.state != RoomState::Suspended {
+            return None;
+        }
+        let (p1, p2) = match self.get_players() {
+            (Some(p1), Some(p2)) => (p1, p2),
+            _ => return None,
+        };
+        let (winner, loser) = if p1.get_id() == p {
+            (p2, p1)
+        } else if p2.get_id() == p {
+            (p1, p2)
+        } else {
+            return None;
+        };
+        if loser.is_connected() || !winner.is_connected() {
+            return None;
+        }
+        // Abandonment is detected by the timer itself, so detach it instead of aborting it
+        self.abandon_timer.lock().unwrap().take();
+        let result = GameResult {
+            player1: winner,
+            player2: loser,
+            result: "abandoned".to_string(),
+        };
+        self.end_game(&result).await;
+        Some(result)
+    }
+    pub fn clock_running(&self) -> bool {
+        // Returns true if the clock of the player to move is counting down
+        match self.state {
+            RoomState::Playing => true,
+            RoomState::Suspended => !self.abandon_policy.pause_clocks,
+            _ => false,
+        }
+    }
+    pub async fn reset_game(&mut self) {
+        // Reset the game to it's initial state
+        self.stop_clocks().await;
+        self.chess_fen = chess::Board::default().to_string();
+        // The next game's seed comes from the last game, so a room replays from its first seed
+        self.seed = self.memory_board.next_seed();
+        self.memory_board = MemoryBoard::new(self.seed);
+        self.turn = String::new();
+        self.turn_count = 0;
+        self.plies = 0;
+        self.draw_offers = DrawOffers::default();
+        self.history = PositionHistory::default();
+        self.takebacks = Takebacks::default();
+        self.consent.clear();
+        self.phase = TurnPhase::default();
+        self.removal = None;
+        self.state = RoomState::Ready;
+        self.assign_colors();
+
+        // Reset player times
+        if self.room_type == RoomType::Casual {
+            return;
+        }
+        for user in [&mut self.p1, &mut self.p2].into_iter().flatten() {
+            user.reset_time().await;
+        }
+    }
+    pub fn get_state(&self) -> RoomState {
+        // Returns the state of the room
+        self.state.clone()
+    }
+    pub fn player_count(&self) -> u32 {
+        // Returns the number of players in the room
+        let mut count = 0;
+        if self.p1.is_some() && self.p1.as_ref().unwrap().is_connected() {
+            count += 1;
+        }
+        if self.p2.is_some() && self.p2.as_ref().unwrap().is_connected() {
+            count += 1;
+        }
+        count
+    }
+    pub fn get_turn(&self) -> Option<String> {
+        // Returns the player whose turn it is
+        if self.turn != String::new() {
+            Some(self.turn.clone())
+        } else {
+            None
+        }
+    }
+    pub fn authorize(&self, p: &str, action: GameAction) -> Result<(), ActionRejected> {
+        // Checks that player p is allowed to perform the given action right now
+        let in_room = [&self.p1, &self.p2]
+            .iter()
+            .any(|u| u.as_ref().is_some_and(|u| u.get_id() == p));
+        if !in_room {
+            return Err(ActionRejected::new(action, RejectReason::NotInRoom));
+        }
+
+        // White's first chess or memory move of a ready room starts the game
+        if self.state == RoomState::Ready
+            && matches!(action, GameAction::MovePiece | GameAction::FlipTile)
+        {
+            if self.turn != p {
+                return Err(ActionRejected::new(action, RejectReason::NotYourTurn));
+            }
+            return Ok(());
+        }
+        if self.state != RoomState::Playing {
+            return Err(ActionRejected::new(action, RejectReason::GameInactive));
+        }
+        if self.turn != p {
+            return Err(ActionRejected::new(action, RejectReason::NotYourTurn));
+        }
+        if !self.phase.allows(action) {
+            return Err(ActionRejected::new(action, RejectReason::WrongPhase));
+        }
+        Ok(())
+    }
+    pub fn advance_phase(&mut self, event: PhaseEvent) -> bool {
+        // Moves the current turn to its next phase
+        // Returns false and leaves the phase untouched if the event is out of order
+        match self.phase.after(event) {
+            Some(phase) => {
+                self.phase = phase;
+                true
+            }
+            None => false,
+        }
+    }
+    pub fn grant_removal(&mut self, entitlement: RemovalEntitlement) {
+        // Lets the current player remove one piece this turn
+        self.removal = Some(entitlement);
+    }
+    pub fn check_removal(&self, piece: Piece, color: Color) -> Result<(), RemovalError> {
+        // Checks that the current player may remove the given piece
+        match &self.removal {
+            Some(entitlement) => entitlement.check(piece, color),
+            None => Err(RemovalError::NoEntitlement),
+        }
+    }
+    pub fn consume_removal(&mut self) -> Option<RemovalEntitlement> {
+        // Uses up the pending removal, it can only be used once
+        self.removal.take()
+    }
+    pub fn decline_removal(&mut self, p: &str, tile: &str) -> Result<(), RemovalError> {
+        // Gives up the pending removal after matching a pair of the player's own pieces
+        let (piece, color) = tile
+            .parse::<Card>()
+            .ok()
+            .and_then(Card::piece)
+            .ok_or(RemovalError::UnknownPiece)?;
+        if self.get_player_color(p) != Some(color) {
+            return Err(RemovalError::NotOwnPiece);
+        }
+        self.check_removal(piece, color)?;
+        self.removal = None;
+        self.advance_phase(PhaseEvent::RemovalDeclined);
+        Ok(())
+    }
+    fn get_user(&self, p: &str) -> Option<&User> {
+        // Returns player p, if they are in the room
+        [&self.p1, &self.p2]
+            .into_iter()
+            .flatten()
+            .find(|u| u.get_id() == p)
+    }
+    pub fn get_seat(&self, p: &str) -> Option<String> {
+        // Returns the seat of player p, which their session token is signed for
+        self.get_user(p).map(|u| u.get_seat())
+    }
+    pub fn set_account(&mut self, p: &str, account: Option<i64>) {
+        // Links player p to their account, or makes them anonymous
+        let user = [&mut self.p1, &mut self.p2]
+            .into_iter()
+            .flatten()
+            .find(|u| u.get_id() == p);
+        if let Some(user) = user {
+            user.set_account(account);
+        }
+    }
+    pub fn get_player_color(&self, p: &str) -> Option<Color> {
+        // Returns the chess color of player p, once colors are given out
+        let user = self.get_user(p)?;
+        match user.get_chess_color().as_str() {
+            "white" => Some(Color::White),
+            "black" => Some(Color::Black),
+            _ => None,
+        }
+    }
+    pub async fn switch_turn(&mut self) {
+        // Switches the turn to the other player
+        if self.state != RoomState::Playing {
+            return;
+        }
+        let p1 = self.p1.as_mut().unwrap();
+        let p2 = self.p2.as_mut().unwrap();
+        // A draw offer is withdrawn once its player moves
+        let mover = if self.turn == p1.get_id() { &*p1 } else { &*p2 };
+        self.draw_offers.expire(&mover.get_chess_color());
+        self.plies += 1;
+
+        // The player who moved gets their increment and delay
+        if self.turn == p1.get_id() {
+            p1.complete_move().await;
+            self.turn = p2.get_id();
+            p2.start_turn().await;
+        } else {
+            p2.complete_move().await;
+            self.turn = p1.get_id();
+            p1.start_turn().await;
+        }
+        self.turn_count += 1;
+        self.phase = TurnPhase::default();
+        // Unused removals expire with the turn
+        self.removal = None;
+        self.stop_memory_timer();
+        let snapshot = self.snapshot().await;
+        self.takebacks.push(snapshot);
+    }
+    fn game_player(&self, p: &str) -> Result<&User, DrawError> {
+        // Returns player p if they can end the game in progress by agreement or resignation
+        let user = self.get_user(p).ok_or(DrawError::NotInRoom)?;
+        if self.state != RoomState::Playing {
+            return Err(DrawError::GameInactive);
+        }
+        Ok(user)
+    }
+    fn result_for(&self, winner: Option<&str>, result: &str) -> Option<GameResult> {
+        // Builds a result with the winner first, or the players in room order for a draw
+        let (mut p1, mut p2) = match self.get_players() {
+            (Some(p1), Some(p2)) => (p1, p2),
+            _ => return None,
+        };
+        if winner.is_some_and(|w| p2.get_id() == w) {
+            std::mem::swap(&mut p1, &mut p2);
+        }
+        Some(GameResult {
+            player1: p1,
+            player2: p2,
+            result: result.to_string(),
+        })
+    }
+    pub async fn resign(&mut self, p: &str) -> Result<GameResult, DrawError> {
+        // Ends the game with a win for the opponent of player p
+        self.game_player(p)?;
+        let winner = [&self.p1, &self.p2]
+            .into_iter()
+            .flatten()
+            .map(|u| u.get_id())
+            .find(|id| id != p);
+        let result = self
+            .result_for(winner.as_deref(), "resignation")
+            .ok_or(DrawError::GameInactive)?;
+        self.end_game(&result).await;
+        Ok(result)
+    }
+    pub fn offer_draw(&mut self, p: &str) -> Result<(), DrawError> {
+        // Opens a draw offer from player p
+        let color = self.game_player(p)?.get_chess_color();
+        self.draw_offers.offer(&color, self.plies)
+    }
+    pub async fn accept_draw(&mut self, p: &str) -> Result<GameResult, DrawError> {
+        // Ends the game in a draw if the opponent of player p has an open offer
+        let color = self.game_player(p)?.get_chess_color();
+        let result = self
+            .result_for(None, "draw_agreed")
+            .ok_or(DrawError::GameInactive)?;
+        self.draw_offers.answer(&color)?;
+        self.end_game(&result).await;
+        Ok(result)
+    }
+    pub fn decline_draw(&mut self, p: &str) -> Result<(), DrawError> {
+        // Turns down the open draw offer made to player p
+        let color = self.game_player(p)?.get_chess_color();
+        self.draw_offers.answer(&color)
+    }
+    pub fn get_draw_offer(&self) -> Option<String> {
+        // Returns the color of the player with an open draw offer
+        self.draw_offers.get_pending()
+    }
+    async fn snapshot(&self) -> TurnSnapshot {
+        // Captures the state at the start of the current turn
+        let (mut white_time, mut black_time) = (0, 0);
+        for user in [&self.p1, &self.p2].into_iter().flatten() {
+            match user.get_chess_color().as_str() {
+                "white" => white_time = user.get_time().await,
+                _ => black_time = user.get_time().await,
+            }
+        }
+        let turn = self
+            .get_user(&self.turn)
+            .map(|u| u.get_chess_color())
+            .unwrap_or_default();
+        TurnSnapshot {
+            chess_fen: self.chess_fen.clone(),
+            memory_board: self.memory_board.clone(),
+            history: self.history.clone(),
+            turn,
+            white_time,
+            black_time,
+            plies: self.plies,
+            turn_count: self.turn_count,
+        }
+    }
+    fn takeback_player(&self, p: &str) -> Result<String, TakebackError> {
+        // Returns the chess color of player p if they can take part in a takeback
+        let user = self.get_user(p).ok_or(TakebackError::NotInRoom)?;
+        if self.state != RoomState::Playing {
+            return Err(TakebackError::GameInactive);
+        }
+        Ok(user.get_chess_color())
+    }
+    pub fn request_takeback(&mut self, p: &str) -> Result<(), TakebackError> {
+        // Asks the opponent of player p to take back p's last turn
+        let color = self.takeback_player(p)?;
+        self.takebacks.request(&color)
+    }
+    pub async fn accept_takeback(&mut self, p: &str) -> Result<(), TakebackError> {
+        // Rolls the game back to the start of the requester's last turn
+        let color = self.takeback_player(p)?;
+        self.takebacks.answer(&color)?;
+        let snapshot = self
+            .takebacks
+            .undo()
+            .ok_or(TakebackError::NothingToTakeBack)?;
+        self.chess_fen = snapshot.chess_fen;
+        self.memory_board = snapshot.memory_board;
+        self.history = snapshot.history;
+        self.plies = snapshot.plies;
+        self.turn_count = snapshot.turn_count;
+        self.phase = TurnPhase::default();
+        self.removal = None;
+        self.stop_memory_timer();
+
+        // Both clocks go back to where they were, the requester's starts running again
+        for user in [&self.p1, &self.p2].into_iter().flatten() {
+            let color = user.get_chess_color();
+            match color.as_str() {
+                "white" => user.set_time(snapshot.white_time).await,
+                _ => user.set_time(snapshot.black_time).await,
+            }
+            if color == snapshot.turn {
+                self.turn = user.get_id();
+                user.start_turn().await;
+            }
+        }
+        Ok(())
+    }
+    pub fn decline_takeback(&mut self, p: &str) -> Result<(), TakebackError> {
+        // Turns down the open takeback request made to player p
+        let color = self.takeback_player(p)?;
+        self.takebacks.answer(&color)
+    }
+    pub fn get_takeback_request(&self) -> Option<String> {
+        // Returns the color of the player with an open takeback request
+        self.takebacks.get_pending()
+    }
+    pub fn get_mut_memory_board(&mut self) -> &mut MemoryBoard {
+        // Returns a mutable reference to the memory board
+        &mut self.memory_board
+    }
+    pub fn get_memory_board(&self) -> MemoryBoard {
+        // Returns the memory board
+        self.memory_board.clone()
+    }
+    pub fn set_memory_board(&mut self, board: MemoryBoard) {
+        // Sets the memory board
+        self.memory_board = board;
+    }
+    pub fn get_chess_board(&self) -> Result<chess::Board, chess::Error> {
+        // Returns the chess board as a string
+        chess::Board::from_str(&self.chess_fen)
+    }
+    pub fn set_chess_board(&mut self, board: chess::Board) {
+        // Sets the chess board from a string
+        self.chess_fen = board.to_string();
+    }
+    pub fn get_seed(&self) -> u64 {
+        // Returns the seed of the current game
+        self.seed
+    }
+    pub fn info(&self) -> serde_json::Value {
+        // Returns the room as clients are allowed to see it, with the memory board redacted
+        // Only fields listed here are sent, the seed, position history and takeback snapshots
+        // would all give away the layout of the memory board
+        serde_json::json!({
+            "p1": self.p1,
+            "p2": self.p2,
+            "chess_fen": self.chess_fen,
+            "memory_board": self.memory_board.redacted(),
+            "turn": self.turn,
+            "turn_count": self.turn_count,
+            "plies": self.plies,
+            "draw_offers": self.draw_offers,
+            "takeback_request": self.get_takeback_request(),
+            "phase": self.phase,
+            "removal": self.removal,
+            "state": self.state,
+            "room_type": self.room_type,
+            "memory_time": self.memory_time,
+            "abandon_policy": self.abandon_policy,
+            "color_assignment": self.color_assignment,
+            "score": self.score,
+            "consent": self.consent,
+            "rated": self.rated,
+            "rating_change": self.rating_change,
+        })
+    }
+    pub async fn validate_move(
+        &mut self,
+        chess_move: ChessMove,
+    ) -> Result<chess::Board, MoveError> {
+        // Returns the board a legal move is played on, starting the game on a ready room's first move
+        // Nothing changes for a rejected move, so white's clock only starts with a legal one
+        let board = self.get_chess_board().map_err(|_| MoveError::IllegalFen)?;
+        if !board.legal(chess_move) {
+            return Err(MoveError::IllegalMove);
+        }
+        if self.state == RoomState::Ready {
+            self.start_game().await;
+        }
+        Ok(board)
+    }
+    pub fn play_move(&mut self, board: &chess::Board, chess_move: ChessMove) -> MoveEffects {
+        // Plays a legal move on the given board and keeps the memory board in step with it
+        let new_board = board.make_move_new(chess_move);
+        let captured = captured_piece(board, chess_move);
+
+        // A captured piece takes its pair of tiles with it, unless the pair was matched earlier
+        let removed_tiles = match captured {
+            Some((piece, color)) => match Card::from_piece(piece, color) {
+                Some(card) => self
+                    .memory_board
+                    .remove_surplus(card, count_pieces(&new_board, piece, color)),
+                None => vec![],
+            },
+            None => vec![],
+        };
+
+        // A promoted pawn's tiles turn into tiles of its new piece
+        let promotion = chess_move
+            .get_promotion()
+            .and_then(|p| PieceKind::try_from(p).ok());
+        if let Some(kind) = promotion {
+            let color = board.side_to_move();
+            let pawns = count_pieces(&new_board, Piece::Pawn, color);
+            if self.memory_board.count(Card::Piece(color, PieceKind::Pawn)) / 2 > pawns {
+                self.memory_board.upgrade_tile(kind, color);
+            }
+        }
+
+        self.set_chess_board(new_board);
+
+        // Pawn moves and captures can't be undone, memory removals are caught by the history
+        let pawn_move = board.piece_on(chess_move.get_source()) == Some(Piece::Pawn);
+        let position = self.position();
+        self.history
+            .record(position, pawn_move || captured.is_some());
+        MoveEffects {
+            captured,
+            removed_tiles,
+        }
+    }
+    fn position(&self) -> Position {
+        // Returns the current position for repetition checks
+        let board = self.get_chess_board().unwrap_or_default();
+        Position::new(&board, self.memory_board.fingerprint(self.seed))
+    }
+    pub fn is_repetition(&self) -> bool {
+        // Returns true if the current position has occurred three times
+        self.history.is_threefold()
+    }
+    pub fn is_fifty_moves(&self) -> bool {
+        // Returns true if the fifty-move rule ends the game
+        self.history.is_fifty_moves()
+    }
+    pub fn audit_memory(&self, room_id: &str) -> AuditReport {
+        // Checks that the memory tiles add up with the chess board
+        match self.get_chess_board() {
+            Ok(board) => audit::audit(room_id, &self.memory_board, &board),
+            Err(_) => AuditReport::default(),
+        }
+    }
+    pub fn reconcile_memory(&mut self, room_id: &str) -> AuditReport {
+        // Audits the memory board and removes tiles of pieces that are gone
+        match self.get_chess_board() {
+            Ok(board) => audit::reconcile(room_id, &mut self.memory_board, &board),
+            Err(_) => AuditReport::default(),
+        }
+    }
+    pub fn get_players(&self) -> (Option<User>, Option<User>) {
+        // Returns the players
+        (self.p1.clone(), self.p2.clone())
+    }
+    pub async fn end_game(&mut self, result: &GameResult) {
+        // Ends the game, stops both clocks and adds the result to the match score
+        self.state = RoomState::Over;
+        self.stop_clocks().await;
+        self.consent.clear();
+        if result.is_draw() {
+            self.score.draws += 1;
+        } else if self
+            .p1
+            .as_ref()
+            .is_some_and(|p1| p1.get_id() == result.player1.get_id())
+        {
+            self.score.p1_wins += 1;
+        } else {
+            self.score.p2_wins += 1;
+        }
+        // Players swap colors for the next game
+        self.creator_white = self.creator_white.map(|white| !white);
+    }
+    pub async fn request_rematch(&mut self, p: &str) -> Result<bool, ConsentError> {
+        // Asks for a rematch of a finished game, the next game starts once both players asked
+        let color = self
+            .get_user(p)
+            .ok_or(ConsentError::NotInRoom)?
+            .get_chess_color();
+        if self.state != RoomState::Over {
+            return Err(ConsentError::GameNotOver);
+        }
+        let agreed = self.consent.ask(&color)?;
+        if agreed {
+            self.reset_game().await;
+        }
+        Ok(agreed)
+    }
+    pub async fn request_reset(&mut self, p: &str) -> Result<bool, ConsentError> {
+        // Resets the game, a game in progress is only reset once both players asked
+        let color = self
+            .get_user(p)
+            .ok_or(ConsentError::NotInRoom)?
+            .get_chess_color();
+        let agreed = match self.state {
+            RoomState::Over => return Err(ConsentError::GameOver),
+            RoomState::Playing | RoomState::Suspended => self.consent.ask(&color)?,
+            RoomState::Waiting => {
+                // Without an opponent the room stays waiting, it can't become ready to play
+                self.reset_game().await;
+                self.state = RoomState::Waiting;
+                return Ok(true);
+            }
+            RoomState::Ready => true,
+        };
+        if agreed {
+            self.reset_game().await;
+        }
+        Ok(agreed)
+    }
+    pub fn get_score(&self) -> MatchScore {
+        // Returns the running score of the room
+        self.score
+    }
+    pub fn get_plies(&self) -> u32 {
+        // Returns the number of chess moves played this game
+        self.plies
+    }
+    pub fn set_rated(&mut self, rated: bool) {
+        // Sets whether finished games move the players' ratings
+        self.rated = rated;
+    }
+    pub fn is_rated(&self) -> bool {
+        // Returns true if finished games move the players' ratings
+        self.rated
+    }
+    pub fn get_rating_pool(&self) -> RatingPool {
+        // Returns the pool the room's games are rated in
+        RatingPool::of(&self.room_type)
+    }
+    pub fn set_rating_change(&mut self, p: &str, delta: RatingDelta) {
+        // Records how the last game moved the rating of player p
+        if self.p1.as_ref().is_some_and(|u| u.get_id() == p) {
+            self.rating_change.p1 = Some(delta);
+        } else if self.p2.as_ref().is_some_and(|u| u.get_id() == p) {
+            self.rating_change.p2 = Some(delta);
+        }
+    }
+    pub fn set_clock(&self, handle: JoinHandle<()>) {
+        // Replaces the room's clock task, aborting the previous one
+        if let Some(old) = self.clock.lock().unwrap().replace(handle) {
+            old.abort();
+        }
+    }
+    pub fn set_memory_timer(&self, handle: JoinHandle<()>) {
+        // Replaces the memory phase timer, aborting the previous one
+        if let Some(old) = self.memory_timer.lock().unwrap().replace(handle) {
+            old.abort();
+        }
+    }
+    pub fn stop_memory_timer(&self) {
+        // Stops the memory phase timer, if one is running
+        if let Some(handle) = self.memory_timer.lock().unwrap().take() {
+            handle.abort();
+        }
+    }
+    pub fn set_memory_time(&mut self, time: Option<u64>) {
+        // Sets the memory phase budget per turn in seconds, None for no limit
+        self.memory_time = time;
+    }
+    pub fn get_memory_time(&self) -> Option<u64> {
+        // Returns the memory phase budget per turn in seconds
+        self.memory_time
+    }
+    pub fn expire_memory_phase(&mut self) -> Option<Vec<usize>> {
+        // Ends the memory phase of the current turn, turning pending tiles back down
+        // Returns the unflipped tiles, or None if the player had already moved on to chess
+        if self.state != RoomState::Playing || self.phase == TurnPhase::AwaitingChess {
+            return None;
+        }
+        self.memory_timer.lock().unwrap().take();
+        self.phase = TurnPhase::AwaitingChess;
+        self.removal = None;
+        Some(self.memory_board.unflip_pending())
+    }
+    pub async fn stop_clocks(&self) {
+        // Stops every timer task of the room and both players' clocks
+        if let Some(handle) = self.clock.lock().unwrap().take() {
+            handle.abort();
+        }
+        if let Some(handle) = self.abandon_timer.lock().unwrap().take() {
+            handle.abort();
+        }
+        self.stop_memory_timer();
+        for user in [&self.p1, &self.p2].into_iter().flatten() {
+            user.end_turn().await;
+        }
+    }
+    pub async fn flag_fall(&mut self) -> Option<GameResult> {
+        // Ends the game if a player has run out of time and returns the result
+        if !self.clock_running() {
+            return None;
+        }
+        let result = self.timeout_result().await?;
+        // Flag fall is detected by the clock task itself, so detach it instead of aborting it
+        self.clock.lock().unwrap().take();
+        self.end_game(&result).await;
+        Some(result)
+    }
+    pub fn get_white(&self) -> Option<User> {
+        // Returns the white player
+        if self.player_count() != 2 {
+            return None;
+        }
+        let p1 = self.p1.as_ref().unwrap();
+        let p2 = self.p2.as_ref().unwrap();
+        if p1.get_chess_color() == "white" {
+            Some(p1.clone())
+        } else {
+            Some(p2.clone())
+        }
+    }
+    pub fn get_black(&self) -> Option<User> {
+        // Returns the black player
+        if self.player_count() != 2 {
+            return None;
+        }
+        let p1 = self.p1.as_ref().unwrap();
+        let p2 = self.p2.as_ref().unwrap();
+        if p1.get_chess_color() == "black" {
+            Some(p1.clone())
+        } else {
+            Some(p2.clone())
+        }
+    }
+    pub fn increment_turns(&mut self) {
+        // Increments the turn count
+        self.turn_count += 1;
+    }
+    pub fn get_time_control(&self) -> TimeControl {
+        // Returns the time control of a timed room, casual rooms have none
+        if let RoomType::Timed(control) = self.room_type {
+            control
+        } else {
+            TimeControl::default()
+        }
+    }
+    pub async fn get_player_times(&self) -> (u64, u64) {
+        // Returns the time left for both players in milliseconds
+        let p1 = self.p1.as_ref();
+        let p2 = self.p2.as_ref();
+        let p1_time = if let Some(p1) = p1 {
+            p1.get_time().await
+        } else {
+            0
+        };
+        let p2_time = if let Some(p2) = p2 {
+            p2.get_time().await
+        } else {
+            0
+        };
+        (p1_time, p2_time)
+    }
+    pub async fn get_turn_time(&self) -> Option<u64> {
+        // Returns the time left in milliseconds for the player whose turn it is
+        let user = [&self.p1, &self.p2]
+            .into_iter()
+            .flatten()
+            .find(|u| u.get_id() == self.turn)?;
+        Some(user.get_time().await)
+    }
+    pub fn get_type(&self) -> RoomType {
+        // Returns the room type
+        self.room_type.clone()
+    }
+    pub async fn timeout(&self) -> bool {
+        // Returns true if a player has run out of time
+        let p1 = self.p1.as_ref();
+        let p2 = self.p2.as_ref();
+        if p1.is_none() || p2.is_none() || self.room_type == RoomType::Casual {
+            return false;
+        }
+        let p1_time = if let Some(p1) = p1 {
+            p1.get_time().await
+        } else {
+            0
+        };
+        let p2_time = if let Some(p2) = p2 {
+            p2.get_time().await
+        } else {
+            0
+        };
+        p1_time == 0 || p2_time == 0
+    }
+    pub async fn timeout_result(&self) -> Option<GameResult> {
+        // Returns the result of the game if a player has run out of time
+        if !self.timeout().await {
+            return None;
+        }
+        let times = self.get_player_times().await;
+        let (mut p1, mut p2) = match self.get_players() {
+            (Some(p1), Some(p2)) => (p1, p2),
+            _ => return None,
+        };
+
+        // The winner goes first, p1 wins unless p2 has time left
+        if times.1 > 0 {
+            std::mem::swap(&mut p1, &mut p2);
+        }
+        Some(GameResult {
+            player1: p1,
+            player2: p2,
+            result: "timeout".to_string(),
+        })
+    }
+
+    pub fn check_win(&self) -> Option<Color> {
+        // If one of the players only has a king left, the other player wins
+
+        let board = self.get_chess_board().unwrap();
+        let mut white = 0;
+        let mut black = 0;
+        for sq in *board.combined() {
+            if board.color_on(sq).unwrap() == Color::White {
+                white += 1;
+            } else {
+                black += 1;
+            }
+        }
+        if white == 1 {
+            Some(Color::Black)
+        } else if black == 1 {
+            Some(Color::White)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chess::Square;
+
+    use super::*;
+
+    const SEED: u64 = 42;
+
+    const ACTIONS: [GameAction; 5] = [
+        GameAction::MovePiece,
+        GameAction::FlipTile,
+        GameAction::MatchTiles,
+        GameAction::MatchPiece,
+        GameAction::ClearSquare,
+    ];
+
+    fn ready_room() -> Room {
+        ready_room_of(RoomType::Casual)
+    }
+
+    fn ready_room_of(room_type: RoomType) -> Room {
+        let mut room = Room::new(
+            "p1".to_string(),
+            "Alice".to_string(),
+            "a".to_string(),
+            0,
+            "red".to_string(),
+            room_type,
+            SEED,
+        );
+        room.set_color_assignment(ColorAssignment::CreatorWhite);
+        room.connect_player(
+            "p2".to_string(),
+            "Bob".to_string(),
+            "b".to_string(),
+            0,
+            "blue".to_string(),
+            None,
+        );
+        room
+    }
+
+    fn phase_allowing(action: GameAction) -> TurnPhase {
+        match action {
+            GameAction::MovePiece | GameAction::FlipTile => TurnPhase::AwaitingMemoryOrChess,
+            GameAction::MatchTiles => TurnPhase::TwoTilesFlipped,
+            GameAction::MatchPiece | GameAction::ClearSquare => TurnPhase::AwaitingRemovalChoice,
+        }
+    }
+
+    #[tokio::test]
+    async fn turn_player_is_authorized_for_every_action() {
+        let mut room = ready_room();
+        room.start_game().await;
+        for action in ACTIONS {
+            room.phase = phase_allowing(action);
+            assert_eq!(room.authorize("p1", action), Ok(()));
+        }
+    }
+
+    #[tokio::test]
+    async fn opponent_is_rejected_for_every_action() {
+        let mut room = ready_room();
+        room.start_game().await;
+        for action in ACTIONS {
+            assert_eq!(
+                room.authorize("p2", action),
+                Err(ActionRejected::new(action, RejectReason::NotYourTurn))
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn turn_passes_to_opponent_after_switch() {
+        let mut room = ready_room();
+        room.start_game().await;
+        room.switch_turn().await;
+        for action in ACTIONS {
+            room.phase = phase_allowing(action);
+            assert_eq!(room.authorize("p2", action), Ok(()));
+            assert_eq!(
+                room.authorize("p1", action),
+                Err(ActionRejected::new(action, RejectReason::NotYourTurn))
+            );
+        }
+    }
+
+    #[test]
+    fn only_opening_actions_are_allowed_before_the_game_starts() {
+        let room = ready_room();
+        assert_eq!(room.authorize("p1", GameAction::MovePiece), Ok(()));
+        assert_eq!(room.authorize("p1", GameAction::FlipTile), Ok(()));
+        // Only white can open the game
+        assert_eq!(
+            room.authorize("p2", GameAction::FlipTile),
+            Err(ActionRejected::new(
+                GameAction::FlipTile,
+                RejectReason::NotYourTurn
+            ))
+        );
+        for action in [
+            GameAction::MatchTiles,
+            GameAction::MatchPiece,
+            GameAction::ClearSquare,
+        ] {
+            assert_eq!(
+                room.authorize("p1", action),
+                Err(ActionRejected::new(action, RejectReason::GameInactive))
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn finished_game_rejects_every_action() {
+        let mut room = ready_room();
+        room.start_game().await;
+        room.resign("p2").await.unwrap();
+        for action in ACTIONS {
+            assert_eq!(
+                room.authorize("p1", action),
+                Err(ActionRejected::new(action, RejectReason::GameInactive))
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn memory_actions_follow_the_turn_phase() {
+        let mut room = ready_room();
+        room.start_game().await;
+        assert_eq!(
+            room.authorize("p1", GameAction::MatchTiles),
+            Err(ActionRejected::new(
+                GameAction::MatchTiles,
+                RejectReason::WrongPhase
+            ))
+        );
+
+        assert!(room.advance_phase(PhaseEvent::TileFlipped));
+        assert!(room.advance_phase(PhaseEvent::TileFlipped));
+        assert_eq!(room.authorize("p1", GameAction::MatchTiles), Ok(()));
+        assert_eq!(
+            room.authorize("p1", GameAction::MovePiece),
+            Err(ActionRejected::new(
+                GameAction::MovePiece,
+                RejectReason::WrongPhase
+            ))
+        );
+
+        assert!(room.advance_phase(PhaseEvent::TilesMatched));
+        assert!(room.advance_phase(PhaseEvent::SquareCleared));
+        assert_eq!(
+            room.authorize("p1", GameAction::FlipTile),
+            Err(ActionRejected::new(
+                GameAction::FlipTile,
+                RejectReason::WrongPhase
+            ))
+        );
+        assert!(!room.advance_phase(PhaseEvent::TilesMatched));
+        assert_eq!(room.phase, TurnPhase::AwaitingChess);
+    }
+
+    #[tokio::test]
+    async fn chess_move_resets_the_phase_for_the_opponent() {
+        let mut room = ready_room();
+        room.start_game().await;
+        room.advance_phase(PhaseEvent::TileFlipped);
+        room.advance_phase(PhaseEvent::TileFlipped);
+        room.advance_phase(PhaseEvent::TilesMismatched);
+        room.switch_turn().await;
+        assert_eq!(room.phase, TurnPhase::AwaitingMemoryOrChess);
+        assert_eq!(room.authorize("p2", GameAction::FlipTile), Ok(()));
+        assert_eq!(
+            room.authorize("p1", GameAction::FlipTile),
+            Err(ActionRejected::new(
+                GameAction::FlipTile,
+                RejectReason::NotYourTurn
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn removal_is_single_use() {
+        let mut room = ready_room();
+        room.start_game().await;
+        assert_eq!(
+            room.check_removal(Piece::Queen, Color::Black),
+            Err(RemovalError::NoEntitlement)
+        );
+
+        room.grant_removal(RemovalEntitlement::Piece("bq".parse().unwrap()));
+        assert_eq!(room.check_removal(Piece::Queen, Color::Black), Ok(()));
+        assert!(room.consume_removal().is_some());
+        assert_eq!(
+            room.check_removal(Piece::Queen, Color::Black),
+            Err(RemovalError::NoEntitlement)
+        );
+    }
+
+    #[tokio::test]
+    async fn unused_removal_expires_with_the_turn() {
+        let mut room = ready_room();
+        room.start_game().await;
+        room.grant_removal(RemovalEntitlement::AnyNonKing);
+        room.switch_turn().await;
+        assert_eq!(
+            room.check_removal(Piece::Pawn, Color::White),
+            Err(RemovalError::NoEntitlement)
+        );
+    }
+
+    fn play(room: &mut Room, from: Square, to: Square) -> MoveEffects {
+        let board = room.get_chess_board().unwrap();
+        let chess_move = ChessMove::new(from, to, None);
+        assert!(board.legal(chess_move));
+        room.play_move(&board, chess_move)
+    }
+
+    #[test]
+    fn en_passant_removes_the_captured_pawns_tiles() {
+        let mut room = ready_room();
+        play(&mut room, Square::E2, Square::E4);
+        play(&mut room, Square::A7, Square::A6);
+        play(&mut room, Square::E4, Square::E5);
+        play(&mut room, Square::D7, Square::D5);
+        let effects = play(&mut room, Square::E5, Square::D6);
+
+        assert_eq!(effects.captured, Some((Piece::Pawn, Color::Black)));
+        assert_eq!(effects.removed_tiles.len(), 2);
+        assert_eq!(room.memory_board.count("bp".parse().unwrap()), 14);
+        assert!(room.audit_memory("room").is_consistent());
+    }
+
+    #[test]
+    fn capture_keeps_tiles_when_the_pair_was_already_matched() {
+        let mut room = ready_room();
+        // A black pawn pair was matched without removing a pawn
+        room.memory_board.remove_tiles("bp".parse().unwrap());
+        play(&mut room, Square::E2, Square::E4);
+        play(&mut room, Square::D7, Square::D5);
+        let effects = play(&mut room, Square::E4, Square::D5);
+
+        assert_eq!(effects.captured, Some((Piece::Pawn, Color::Black)));
+        assert!(effects.removed_tiles.is_empty());
+        assert_eq!(room.memory_board.count("bp".parse().unwrap()), 14);
+        assert!(room.audit_memory("room").is_consistent());
+    }
+
+    #[test]
+    fn promotion_turns_pawn_tiles_into_the_new_piece() {
+        let mut room = ready_room();
+        room.set_chess_board("4k3/1P6/8/8/8/8/PPPPPPP1/4K3 w - - 0 1".parse().unwrap());
+        let board = room.get_chess_board().unwrap();
+        room.play_move(
+            &board,
+            ChessMove::new(Square::B7, Square::B8, Some(Piece::Queen)),
+        );
+        assert_eq!(room.memory_board.count("wq".parse().unwrap()), 4);
+        assert_eq!(room.memory_board.count("wp".parse().unwrap()), 14);
+    }
+
+    #[test]
+    fn room_info_hides_the_memory_board() {
+        let room = ready_room();
+        let info = room.info();
+        let tiles = info["memory_board"]["board"].as_array().unwrap();
+        assert_eq!(tiles.len(), 64);
+        assert!(tiles.iter().all(|tile| tile == "?"));
+        assert!(info.get("seed").is_none());
+        assert!(info["memory_board"].get("rng").is_none());
+        assert!(info.get("takebacks").is_none());
+    }
+
+    #[tokio::test]
+    async fn room_info_gives_away_no_face_down_tile() {
+        let mut room = ready_room();
+        room.start_game().await;
+        let board = room.get_chess_board().unwrap();
+        let e4 = ChessMove::new(Square::E2, Square::E4, None);
+        room.play_move(&board, e4);
+        room.switch_turn().await;
+
+        // Nothing was flipped, so no card code may show up, neither as a tile nor in a layout
+        let info = room.info().to_string();
+        let codes = ["w", "b"]
+            .iter()
+            .flat_map(|color| {
+                "pnbrqk"
+                    .chars()
+                    .map(move |kind| format!("{}{}", color, kind))
+            })
+            .chain(["x".to_string()]);
+        for code in codes {
+            assert!(
+                !info.contains(&format!("\"{}\"", code)),
+                "{} in {}",
+                code,
+                info
+            );
+            assert!(
+                !info.contains(&format!("{},", code)),
+                "{} in {}",
+                code,
+                info
+            );
+        }
+        assert!(info.contains("\"?\""));
+    }
+
+    #[tokio::test]
+    async fn rooms_with_the_same_seed_replay_the_same_games() {
+        let mut a = ready_room();
+        let mut b = ready_room();
+        assert_eq!(a.memory_board, b.memory_board);
+
+        a.reset_game().await;
+        b.reset_game().await;
+        assert_ne!(a.get_seed(), SEED);
+        assert_eq!(a.get_seed(), b.get_seed());
+        assert_eq!(a.memory_board, b.memory_board);
+    }
+
+    #[tokio::test]
+    async fn own_pair_match_can_be_declined() {
+        let mut room = ready_room();
+        room.start_game().await;
+        room.phase = TurnPhase::AwaitingRemovalChoice;
+        room.grant_removal(RemovalEntitlement::Piece("wn".parse().unwrap()));
+
+        assert_eq!(room.decline_removal("p1", "wn"), Ok(()));
+        assert_eq!(room.phase, TurnPhase::AwaitingChess);
+        assert_eq!(room.consume_removal(), None);
+    }
+
+    #[tokio::test]
+    async fn decline_rejects_unknown_and_unauthorized_pieces() {
+        let mut room = ready_room();
+        room.start_game().await;
+        room.phase = TurnPhase::AwaitingRemovalChoice;
+
+        assert_eq!(
+            room.decline_removal("p1", "wn"),
+            Err(RemovalError::NoEntitlement)
+        );
+
+        room.grant_removal(RemovalEntitlement::Piece("bn".parse().unwrap()));
+        assert_eq!(
+            room.decline_removal("p1", "zz"),
+            Err(RemovalError::UnknownPiece)
+        );
+        assert_eq!(
+            room.decline_removal("p1", "wk"),
+            Err(RemovalError::UnknownPiece)
+        );
+        assert_eq!(
+            room.decline_removal("p1", "bn"),
+            Err(RemovalError::NotOwnPiece)
+        );
+        assert_eq!(
+            room.decline_removal("p1", "wq"),
+            Err(RemovalError::WrongPiece)
+        );
+        assert_eq!(room.phase, TurnPhase::AwaitingRemovalChoice);
+        assert!(room.consume_removal().is_some());
+    }
+
+    #[tokio::test]
+    async fn unknown_player_is_rejected() {
+        let mut room = ready_room();
+        room.start_game().await;
+        assert_eq!(
+            room.authorize("spectator", GameAction::MovePiece),
+            Err(ActionRejected::new(
+                GameAction::MovePiece,
+                RejectReason::NotInRoom
+            ))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn flag_fall_ends_the_game_once() {
+        let mut room = ready_room_of(RoomType::Timed(TimeControl {
+            base: 60,
+            ..TimeControl::default()
+        }));
+        room.set_color_assignment(ColorAssignment::CreatorBlack);
+        room.assign_colors();
+        room.start_game().await;
+        assert!(room.flag_fall().await.is_none());
+
+        tokio::time::advance(tokio::time::Duration::from_secs(60)).await;
+        let result = room.flag_fall().await.unwrap();
+        assert_eq!(result.result, "timeout");
+        assert_eq!(result.player1.get_id(), "p1");
+        assert_eq!(room.get_state(), RoomState::Over);
+        assert!(room.flag_fall().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn casual_rooms_never_time_out() {
+        let mut room = ready_room();
+        room.start_game().await;
+        assert!(room.flag_fall().await.is_none());
+        assert_eq!(room.get_state(), RoomState::Playing);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn player_times_are_kept_in_milliseconds() {
+        let mut room = ready_room_of(RoomType::Timed(TimeControl {
+            base: 60,
+            ..TimeControl::default()
+        }));
+        room.start_game().await;
+        tokio::time::advance(tokio::time::Duration::from_millis(1_500)).await;
+        assert_eq!(room.get_player_times().await, (58_500, 60_000));
+
+        room.switch_turn().await;
+        tokio::time::advance(tokio::time::Duration::from_millis(250)).await;
+        assert_eq!(room.get_player_times().await, (58_500, 59_750));
+        assert_eq!(room.get_turn_time().await, Some(59_750));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn running_out_of_time_flags_the_player_to_move() {
+        let mut room = ready_room_of(RoomType::Timed(TimeControl {
+            base: 60,
+            ..TimeControl::default()
+        }));
+        room.start_game().await;
+        tokio::time::advance(tokio::time::Duration::from_millis(59_999)).await;
+        assert!(room.flag_fall().await.is_none());
+
+        tokio::time::advance(tokio::time::Duration::from_millis(1)).await;
+        let result = room.flag_fall().await.unwrap();
+        assert_eq!(result.player1.get_id(), "p2");
+        assert_eq!(room.get_player_times().await, (0, 60_000));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn switching_turns_applies_the_increment() {
+        let control = TimeControl {
+            base: 180,
+            increment: 2,
+            ..TimeControl::default()
+        };
+        let mut room = ready_room_of(RoomType::Timed(control));
+        assert_eq!(room.get_type().to_string(), "timed 3+2");
+        room.start_game().await;
+        tokio::time::advance(tokio::time::Duration::from_secs(5)).await;
+        room.switch_turn().await;
+        assert_eq!(room.get_player_times().await, (177_000, 180_000));
+    }
+
+    #[tokio::test]
+    async fn expired_memory_phase_unflips_pending_tiles() {
+        let mut room = ready_room();
+        room.start_game().await;
+        room.memory_board.flip_tile(3).unwrap();
+        room.advance_phase(PhaseEvent::TileFlipped);
+
+        assert_eq!(room.expire_memory_phase(), Some(vec![3]));
+        assert_eq!(room.phase, TurnPhase::AwaitingChess);
+        assert!(room.memory_board.get_flips().is_empty());
+        assert_eq!(room.info()["memory_board"]["board"][3], "?");
+        assert!(room.authorize("p1", GameAction::FlipTile).is_err());
+        assert_eq!(room.authorize("p1", GameAction::MovePiece), Ok(()));
+
+        // Once in the chess phase there is nothing left to expire
+        assert_eq!(room.expire_memory_phase(), None);
+    }
+
+    #[tokio::test]
+    async fn expired_memory_phase_drops_a_pending_removal() {
+        let mut room = ready_room();
+        room.start_game().await;
+        room.phase = TurnPhase::AwaitingRemovalChoice;
+        room.grant_removal(RemovalEntitlement::AnyNonKing);
+
+        assert_eq!(room.expire_memory_phase(), Some(vec![]));
+        assert_eq!(
+            room.check_removal(Piece::Pawn, Color::Black),
+            Err(RemovalError::NoEntitlement)
+        );
+    }
+
+    async fn timed_game_after_first_move(policy: AbandonPolicy) -> Room {
+        let mut room = ready_room_of(RoomType::Timed(TimeControl {
+            base: 60,
+            ..TimeControl::default()
+        }));
+        room.set_abandon_policy(policy);
+        room.start_game().await;
+        play(&mut room, Square::E2, Square::E4);
+        room.switch_turn().await;
+        room
+    }
+
+    fn reconnect_p2(room: &mut Room) {
+        let seat = room.get_players().1.map(|p2| p2.get_seat());
+        room.connect_player(
+            "p2-again".to_string(),
+            "Bob".to_string(),
+            "b".to_string(),
+            0,
+            "blue".to_string(),
+            seat,
+        );
+    }
+
+    #[tokio::test]
+    async fn games_back_at_the_start_position_still_have_moves() {
+        let mut room = ready_room();
+        room.start_game().await;
+        for (from, to) in [
+            (Square::G1, Square::F3),
+            (Square::G8, Square::F6),
+            (Square::F3, Square::G1),
+            (Square::F6, Square::G8),
+        ] {
+            play(&mut room, from, to);
+            room.switch_turn().await;
+        }
+        assert_eq!(room.get_chess_board().unwrap(), chess::Board::default());
+        assert!(room.has_moves());
+        assert_eq!(
+            room.disconnect_player("p2".to_string()).await,
+            Departure::Suspended
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_ids_disconnect_nobody() {
+        let mut room = timed_game_after_first_move(AbandonPolicy::default()).await;
+        reconnect_p2(&mut room);
+        // The socket p2 had before reclaiming the seat goes away late
+        assert_eq!(
+            room.disconnect_player("p2".to_string()).await,
+            Departure::NoGame
+        );
+        assert_eq!(
+            room.disconnect_player("spectator".to_string()).await,
+            Departure::NoGame
+        );
+        assert_eq!(room.player_count(), 2);
+        assert_eq!(room.get_state(), RoomState::Playing);
+    }
+
+    #[tokio::test]
+    async fn leaving_before_any_move_aborts_the_game() {
+        let mut room = ready_room();
+        room.start_game().await;
+        assert_eq!(
+            room.disconnect_player("p2".to_string()).await,
+            Departure::Aborted
+        );
+        assert_eq!(room.get_state(), RoomState::Waiting);
+        assert!(!room.has_moves());
+        // Colors of the next game are already given out
+        assert_eq!(room.get_turn(), Some("p1".to_string()));
+    }
+
+    #[tokio::test]
+    async fn colors_are_given_out_when_the_room_fills() {
+        let mut room = Room::new(
+            "p1".to_string(),
+            "Alice".to_string(),
+            "a".to_string(),
+            0,
+            "red".to_string(),
+            RoomType::Casual,
+            SEED,
+        );
+        room.set_color_assignment(ColorAssignment::CreatorBlack);
+        assert_eq!(room.assign_colors(), None);
+        room.connect_player(
+            "p2".to_string(),
+            "Bob".to_string(),
+            "b".to_string(),
+            0,
+            "blue".to_string(),
+            None,
+        );
+        assert_eq!(room.get_state(), RoomState::Ready);
+        assert_eq!(room.get_turn(), Some("p2".to_string()));
+        assert_eq!(room.get_player_color("p1"), Some(Color::Black));
+
+        room.start_game().await;
+        assert_eq!(room.get_turn(), Some("p2".to_string()));
+        assert_eq!(room.get_white().unwrap().get_id(), "p2");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn clocks_pause_while_a_player_is_away() {
+        let mut room = timed_game_after_first_move(AbandonPolicy::default()).await;
+        assert_eq!(
+            room.disconnect_player("p2".to_string()).await,
+            Departure::Suspended
+        );
+        tokio::time::advance(tokio::time::Duration::from_secs(10)).await;
+        assert_eq!(room.get_player_times().await, (60_000, 60_000));
+        assert!(room.flag_fall().await.is_none());
+
+        // Coming back restarts the clock of the player to move
+        reconnect_p2(&mut room);
+        assert_eq!(room.get_state(), RoomState::Playing);
+        room.resume_clocks().await;
+        tokio::time::advance(tokio::time::Duration::from_secs(1)).await;
+        assert_eq!(room.get_player_times().await, (60_000, 59_000));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn clocks_can_keep_running_while_a_player_is_away() {
+        let policy = AbandonPolicy {
+            pause_clocks: false,
+            grace_period: None,
+        };
+        let mut room = timed_game_after_first_move(policy).await;
+        room.disconnect_player("p2".to_string()).await;
+        tokio::time::advance(tokio::time::Duration::from_secs(60)).await;
+        let result = room.flag_fall().await.unwrap();
+        assert_eq!(result.player1.get_id(), "p1");
+    }
+
+    #[tokio::test]
+    async fn abandoned_game_goes_to_the_player_who_stayed() {
+        let mut room = timed_game_after_first_move(AbandonPolicy::default()).await;
+        room.disconnect_player("p2".to_string()).await;
+        assert!(room.abandon("p1").await.is_none());
+
+        let result = room.abandon("p2").await.unwrap();
+        assert_eq!(result.result, "abandoned");
+        assert_eq!(result.player1.get_id(), "p1");
+        assert_eq!(room.get_state(), RoomState::Over);
+    }
+
+    #[tokio::test]
+    async fn returning_player_keeps_their_game() {
+        let mut room = timed_game_after_first_move(AbandonPolicy::default()).await;
+        room.disconnect_player("p2".to_string()).await;
+        reconnect_p2(&mut room);
+        assert!(room.abandon("p2").await.is_none());
+        assert_eq!(room.get_turn(), Some("p2-again".to_string()));
+    }
+
+    async fn game_in_progress() -> Room {
+        let mut room = ready_room();
+        room.start_game().await;
+        room
+    }
+
+    #[tokio::test]
+    async fn resigning_hands_the_win_to_the_opponent() {
+        let mut room = game_in_progress().await;
+        let result = room.resign("p1").await.unwrap();
+        assert_eq!(result.result, "resignation");
+        assert_eq!(result.player1.get_id(), "p2");
+        assert_eq!(room.get_state(), RoomState::Over);
+        assert_eq!(
+            room.resign("p2").await.unwrap_err(),
+            DrawError::GameInactive
+        );
+    }
+
+    #[tokio::test]
+    async fn results_carry_the_players_accounts() {
+        let mut room = game_in_progress().await;
+        room.set_account("p2", Some(7));
+        let result = room.resign("p1").await.unwrap();
+        assert_eq!(result.player1.get_account(), Some(7));
+        assert_eq!(result.player2.get_account(), None);
+    }
+
+    #[tokio::test]
+    async fn rating_changes_last_until_the_next_game() {
+        let mut room = game_in_progress().await;
+        room.resign("p1").await.unwrap();
+        room.set_rating_change(
+            "p2",
+            RatingDelta {
+                rating: 1662,
+                change: 162,
+            },
+        );
+        assert_eq!(room.info()["rating_change"]["p2"]["change"], 162);
+        assert!(room.info()["rating_change"]["p1"].is_null());
+        room.request_rematch("p1").await.unwrap();
+        room.request_rematch("p2").await.unwrap();
+        room.start_game().await;
+        assert!(room.info()["rating_change"]["p2"].is_null());
+    }
+
+    #[tokio::test]
+    async fn agreed_draw_ends_the_game() {
+        let mut room = game_in_progress().await;
+        assert_eq!(
+            room.accept_draw("p2").await.unwrap_err(),
+            DrawError::NoOffer
+        );
+        room.offer_draw("p1").unwrap();
+        assert_eq!(
+            room.accept_draw("p1").await.unwrap_err(),
+            DrawError::OwnOffer
+        );
+        let result = room.accept_draw("p2").await.unwrap();
+        assert_eq!(result.result, "draw_agreed");
+        assert_eq!(room.get_state(), RoomState::Over);
+    }
+
+    #[tokio::test]
+    async fn draw_offer_expires_when_the_offerer_moves() {
+        let mut room = game_in_progress().await;
+        room.offer_draw("p1").unwrap();
+        assert_eq!(room.get_draw_offer(), Some("white".to_string()));
+
+        room.switch_turn().await;
+        assert_eq!(room.get_draw_offer(), None);
+        assert_eq!(room.decline_draw("p2"), Err(DrawError::NoOffer));
+
+        // Offering again has to wait a few moves
+        assert_eq!(room.offer_draw("p1"), Err(DrawError::TooSoon));
+        assert_eq!(room.offer_draw("stranger"), Err(DrawError::NotInRoom));
+    }
+
+    #[tokio::test]
+    async fn shuffling_knights_repeats_the_start_position() {
+        let mut room = game_in_progress().await;
+        for _ in 0..2 {
+            assert!(!room.is_repetition());
+            play(&mut room, Square::G1, Square::F3);
+            play(&mut room, Square::G8, Square::F6);
+            play(&mut room, Square::F3, Square::G1);
+            play(&mut room, Square::F6, Square::G8);
+        }
+        assert!(room.is_repetition());
+        assert!(!room.is_fifty_moves());
+    }
+
+    #[tokio::test]
+    async fn removing_memory_tiles_breaks_the_repetition() {
+        let mut room = game_in_progress().await;
+        play(&mut room, Square::G1, Square::F3);
+        play(&mut room, Square::G8, Square::F6);
+        play(&mut room, Square::F3, Square::G1);
+        play(&mut room, Square::F6, Square::G8);
+        room.get_mut_memory_board()
+            .remove_tiles(Card::Piece(Color::White, PieceKind::Pawn));
+        for _ in 0..2 {
+            play(&mut room, Square::G1, Square::F3);
+            play(&mut room, Square::G8, Square::F6);
+            play(&mut room, Square::F3, Square::G1);
+            play(&mut room, Square::F6, Square::G8);
+        }
+        assert!(!room.is_repetition());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn takeback_restores_both_boards_and_the_clocks() {
+        let mut room = ready_room_of(RoomType::Timed(TimeControl {
+            base: 60,
+            increment: 5,
+            ..TimeControl::default()
+        }));
+        room.start_game().await;
+        tokio::time::advance(tokio::time::Duration::from_secs(2)).await;
+        let board = room.get_memory_board();
+
+        // White matches a pair and moves, black starts thinking
+        room.get_mut_memory_board()
+            .remove_tiles(Card::Piece(Color::Black, PieceKind::Knight));
+        play(&mut room, Square::E2, Square::E4);
+        room.switch_turn().await;
+        tokio::time::advance(tokio::time::Duration::from_secs(3)).await;
+        assert_eq!(room.get_player_times().await, (63_000, 57_000));
+
+        assert_eq!(
+            room.request_takeback("p2"),
+            Err(TakebackError::NothingToTakeBack)
+        );
+        room.request_takeback("p1").unwrap();
+        assert_eq!(
+            room.accept_takeback("p1").await,
+            Err(TakebackError::OwnRequest)
+        );
+        room.accept_takeback("p2").await.unwrap();
+
+        assert_eq!(room.get_chess_board().unwrap(), chess::Board::default());
+        assert_eq!(room.get_memory_board(), board);
+        assert_eq!(room.get_turn(), Some("p1".to_string()));
+        assert_eq!(room.get_player_times().await, (60_000, 60_000));
+        // White's clock runs again
+        tokio::time::advance(tokio::time::Duration::from_secs(1)).await;
+        assert_eq!(room.get_player_times().await, (59_000, 60_000));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn snapshots_resume_after_a_restart() {
+        let mut room = ready_room_of(RoomType::Timed(TimeControl {
+            base: 60,
+            ..TimeControl::default()
+        }));
+        room.start_game().await;
+        play(&mut room, Square::E2, Square::E4);
+        room.switch_turn().await;
+        tokio::time::advance(tokio::time::Duration::from_secs(3)).await;
+        room.get_mut_memory_board().flip_tile(0);
+        let seat = room.get_seat("p2").unwrap();
+
+        let mut saved = room.clone();
+        saved.save_clocks().await;
+        let json = serde_json::to_string(&saved).unwrap();
+        let mut restored: Room = serde_json::from_str(&json).unwrap();
+        restored.restore().await;
+
+        assert_eq!(restored.get_state(), RoomState::Suspended);
+        assert_eq!(restored.player_count(), 0);
+        assert_eq!(
+            restored.get_chess_board().unwrap(),
+            room.get_chess_board().unwrap()
+        );
+        assert_eq!(restored.get_memory_board(), room.get_memory_board());
+        // The clocks stay stopped until both players are back
+        tokio::time::advance(tokio::time::Duration::from_secs(5)).await;
+        assert_eq!(restored.get_player_times().await, (60_000, 57_000));
+
+        restored.connect_player(
+            "p2b".to_string(),
+            "Bob".to_string(),
+            "b".to_string(),
+            0,
+            "blue".to_string(),
+            Some(seat),
+        );
+        assert_eq!(restored.get_state(), RoomState::Suspended);
+        assert_eq!(restored.get_turn(), Some("p2b".to_string()));
+        assert!(!restored.is_abandoned());
+
+        // Nobody comes back for the other seat, which expires with its token
+        tokio::time::advance(tokio::time::Duration::from_secs(
+            crate::session::SESSION_TTL,
+        ))
+        .await;
+        assert!(!restored.is_abandoned());
+        restored.disconnect_player("p2b".to_string()).await;
+        tokio::time::advance(tokio::time::Duration::from_secs(
+            crate::session::SESSION_TTL,
+        ))
+        .await;
+        assert!(restored.is_abandoned());
+    }
+
+    #[tokio::test]
+    async fn restored_games_without_moves_are_aborted() {
+        let mut room = ready_room();
+        room.start_game().await;
+        let json = serde_json::to_string(&room).unwrap();
+        let mut restored: Room = serde_json::from_str(&json).unwrap();
+        restored.restore().await;
+        assert_eq!(restored.get_state(), RoomState::Waiting);
+        assert!(!restored.has_moves());
+    }
+
+    #[tokio::test]
+    async fn king_only_wins_count_for_the_winner() {
+        let mut room = ready_room();
+        room.start_game().await;
+        // p1 plays white and leaves black with a lone king
+        let board: chess::Board = "4k3/8/8/8/8/8/8/Q3K3 b - - 0 1".parse().unwrap();
+        room.set_chess_board(board);
+        let result = crate::chess::util::is_game_over(&board, &room).unwrap();
+        assert_eq!(result.result, "game_over");
+        room.end_game(&result).await;
+        assert_eq!(room.get_score().p1_wins, 1);
+        assert_eq!(room.get_score().p2_wins, 0);
+    }
+
+    #[tokio::test]
+    async fn rematch_swaps_colors_and_keeps_score() {
+        let mut room = ready_room();
+        room.start_game().await;
+        assert_eq!(
+            room.request_rematch("p1").await,
+            Err(ConsentError::GameNotOver)
+        );
+        room.resign("p2").await.unwrap();
+
+        assert_eq!(room.request_rematch("p1").await, Ok(false));
+        assert_eq!(
+            room.request_rematch("p1").await,
+            Err(ConsentError::AlreadyAsked)
+        );
+        assert_eq!(room.request_rematch("p2").await, Ok(true));
+        assert_eq!(room.get_state(), RoomState::Ready);
+        assert_eq!(room.get_turn(), Some("p2".to_string()));
+
+        room.start_game().await;
+        room.offer_draw("p2").unwrap();
+        room.accept_draw("p1").await.unwrap();
+        assert_eq!(
+            room.get_score(),
+            MatchScore {
+                p1_wins: 1,
+                p2_wins: 0,
+                draws: 1
+            }
+        );
+        assert_eq!(room.info()["score"]["draws"], 1);
+
+        room.request_rematch("p2").await.unwrap();
+        room.request_rematch("p1").await.unwrap();
+        assert_eq!(room.get_turn(), Some("p1".to_string()));
+    }
+
+    #[tokio::test]
+    async fn resetting_a_game_in_progress_needs_both_players() {
+        let mut room = ready_room();
+        assert_eq!(room.request_reset("p2").await, Ok(true));
+        room.start_game().await;
+        play(&mut room, Square::E2, Square::E4);
+        room.switch_turn().await;
+
+        assert_eq!(room.request_reset("p1").await, Ok(false));
+        assert!(room.has_moves());
+        assert_eq!(room.request_reset("p2").await, Ok(true));
+        assert!(!room.has_moves());
+        // An aborted game doesn't count towards the score or swap colors
+        assert_eq!(room.get_score().games(), 0);
+        assert_eq!(room.get_turn(), Some("p1".to_string()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn illegal_first_moves_leave_the_room_ready() {
+        let mut room = ready_room_of(RoomType::Timed(TimeControl {
+            base: 60,
+            ..TimeControl::default()
+        }));
+        let illegal = ChessMove::new(Square::E2, Square::E5, None);
+        assert_eq!(
+            room.validate_move(illegal).await,
+            Err(MoveError::IllegalMove)
+        );
+        assert_eq!(room.get_state(), RoomState::Ready);
+        tokio::time::advance(tokio::time::Duration::from_secs(5)).await;
+        assert_eq!(room.get_player_times().await, (60_000, 60_000));
+
+        let e4 = ChessMove::new(Square::E2, Square::E4, None);
+        assert!(room.validate_move(e4).await.is_ok());
+        assert_eq!(room.get_state(), RoomState::Playing);
+        tokio::time::advance(tokio::time::Duration::from_secs(5)).await;
+        assert_eq!(room.get_player_times().await, (55_000, 60_000));
+    }
+
+    #[tokio::test]
+    async fn resetting_with_a_lone_creator_keeps_the_room_waiting() {
+        for room_type in [RoomType::Casual, RoomType::Timed(TimeControl::default())] {
+            let mut room = Room::new(
+                "p1".to_string(),
+                "Alice".to_string(),
+                "a".to_string(),
+                0,
+                "red".to_string(),
+                room_type,
+                SEED,
+            );
+            assert_eq!(room.request_reset("p1").await, Ok(true));
+            assert_eq!(room.get_state(), RoomState::Waiting);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn only_the_seat_token_gets_a_player_back_in() {
+        let mut room = timed_game_after_first_move(AbandonPolicy::default()).await;
+        let seat = room.get_seat("p2");
+        room.disconnect_player("p2".to_string()).await;
+
+        // Copying the avatar isn't enough anymore
+        let impostor = room.connect_player(
+            "impostor".to_string(),
+            "Bob".to_string(),
+            "b".to_string(),
+            0,
+            "blue".to_string(),
+            Some("forged".to_string()),
+        );
+        assert_eq!(impostor, None);
+        assert_eq!(room.get_state(), RoomState::Suspended);
+
+        assert_eq!(
+            room.connect_player(
+                "p2-again".to_string(),
+                "Bob".to_string(),
+                "b".to_string(),
+                0,
+                "blue".to_string(),
+                seat.clone(),
+            ),
+            seat
+        );
+        assert_eq!(room.get_turn(), Some("p2-again".to_string()));
+        assert_eq!(room.get_state(), RoomState::Playing);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn expired_seats_go_to_newcomers() {
+        let mut room = ready_room();
+        let seat = room.get_seat("p2");
+        room.disconnect_player("p2".to_string()).await;
+        tokio::time::advance(tokio::time::Duration::from_secs(
+            crate::session::SESSION_TTL,
+        ))
+        .await;
+
+        let newcomer = room.connect_player(
+            "p3".to_string(),
+            "Carol".to_string(),
+            "c".to_string(),
+            0,
+            "green".to_string(),
+            None,
+        );
+        assert!(newcomer.is_some());
+        assert_ne!(newcomer, seat);
+        assert_eq!(room.get_players().1.unwrap().get_id(), "p3");
+        // The old token is worthless now
+        assert_eq!(room.get_seat("p2"), None);
+    }
+}
@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+
+use super::action::GameAction;
+
+// Phases a single turn goes through
+// A turn starts with an optional memory move (two flips and a match, then an optional
+// removal) and always ends with a chess move
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum TurnPhase {
+    #[default]
+    AwaitingMemoryOrChess,
+    OneTileFlipped,
+    TwoTilesFlipped,
+    AwaitingRemovalChoice,
+    AwaitingChess,
+}
+
+// Outcomes of memory actions that move a turn to its next phase
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PhaseEvent {
+    TileFlipped,
+    TilesMatched,
+    TilesMismatched,
+    SquareCleared,
+    RemovalDeclined,
+}
+
+impl TurnPhase {
+    pub fn allows(self, action: GameAction) -> bool {
+        // Returns true if the action can be taken during this phase
+        match self {
+            TurnPhase::AwaitingMemoryOrChess => {
+                matches!(action, GameAction::FlipTile | GameAction::MovePiece)
+            }
+            TurnPhase::OneTileFlipped => action == GameAction::FlipTile,
+            TurnPhase::TwoTilesFlipped => action == GameAction::MatchTiles,
+            TurnPhase::AwaitingRemovalChoice => matches!(
+                action,
+                GameAction::ClearSquare | GameAction::MatchPiece | GameAction::MovePiece
+            ),
+            TurnPhase::AwaitingChess => action == GameAction::MovePiece,
+        }
+    }
+
+    pub fn after(self, event: PhaseEvent) -> Option<TurnPhase> {
+        // Returns the phase that follows the event, or None if the event can't happen now
+        match (self, event) {
+            (TurnPhase::AwaitingMemoryOrChess, PhaseEvent::TileFlipped) => {
+                Some(TurnPhase::OneTileFlipped)
+            }
+            (TurnPhase::OneTileFlipped, PhaseEvent::TileFlipped) => {
+                Some(TurnPhase::TwoTilesFlipped)
+            }
+            (TurnPhase::TwoTilesFlipped, PhaseEvent::TilesMatched) => {
+                Some(TurnPhase::AwaitingRemovalChoice)
+            }
+            (TurnPhase::TwoTilesFlipped, PhaseEvent::TilesMismatched) => {
+                Some(TurnPhase::AwaitingChess)
+            }
+            (
+                TurnPhase::AwaitingRemovalChoice,
+                PhaseEvent::SquareCleared | PhaseEvent::RemovalDeclined,
+            ) => Some(TurnPhase::AwaitingChess),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_memory_move_ends_awaiting_chess() {
+        let phase = TurnPhase::default()
+            .after(PhaseEvent::TileFlipped)
+            .and_then(|p| p.after(PhaseEvent::TileFlipped))
+            .and_then(|p| p.after(PhaseEvent::TilesMatched))
+            .and_then(|p| p.after(PhaseEvent::SquareCleared));
+        assert_eq!(phase, Some(TurnPhase::AwaitingChess));
+    }
+
+    #[test]
+    fn mismatch_skips_removal() {
+        assert_eq!(
+            TurnPhase::TwoTilesFlipped.after(PhaseEvent::TilesMismatched),
+            Some(TurnPhase::AwaitingChess)
+        );
+    }
+
+    #[test]
+    fn only_one_memory_move_per_turn() {
+        assert!(!TurnPhase::AwaitingChess.allows(GameAction::FlipTile));
+        assert_eq!(
+            TurnPhase::AwaitingChess.after(PhaseEvent::TileFlipped),
+            None
+        );
+        assert_eq!(
+            TurnPhase::AwaitingChess.after(PhaseEvent::TilesMatched),
+            None
+        );
+    }
+
+    #[test]
+    fn chess_move_waits_for_pending_flips() {
+        assert!(!TurnPhase::OneTileFlipped.allows(GameAction::MovePiece));
+        assert!(!TurnPhase::TwoTilesFlipped.allows(GameAction::MovePiece));
+        assert!(TurnPhase::AwaitingRemovalChoice.allows(GameAction::MovePiece));
+    }
+
+    #[test]
+    fn removal_needs_a_match() {
+        for phase in [
+            TurnPhase::AwaitingMemoryOrChess,
+            TurnPhase::OneTileFlipped,
+            TurnPhase::TwoTilesFlipped,
+            TurnPhase::AwaitingChess,
+        ] {
+            assert!(!phase.allows(GameAction::ClearSquare));
+            assert_eq!(phase.after(PhaseEvent::SquareCleared), None);
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+
+// Running score of the games played in a room, counted per player slot
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq)]
+pub struct MatchScore {
+    pub p1_wins: u32,
+    pub p2_wins: u32,
+    pub draws: u32,
+}
+
+// A request that needs both players, i.e. a rematch or resetting a game in progress
+// pending holds the chess color of the player who asked, the other player agrees by asking too
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct Consent {
+    pending: Option<String>,
+}
+
+// Reasons for refusing a rematch or reset event
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConsentError {
+    NotInRoom,
+    GameNotOver,
+    GameOver,
+    AlreadyAsked,
+}
+
+impl MatchScore {
+    pub fn games(&self) -> u32 {
+        // Returns the number of finished games
+        self.p1_wins + self.p2_wins + self.draws
+    }
+}
+
+impl Consent {
+    pub fn ask(&mut self, color: &str) -> Result<bool, ConsentError> {
+        // Records that a player asked, returns true once both players have
+        match &self.pending {
+            None => {
+                self.pending = Some(color.to_string());
+                Ok(false)
+            }
+            Some(asked) if asked == color => Err(ConsentError::AlreadyAsked),
+            Some(_) => {
+                self.pending = None;
+                Ok(true)
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        // Drops the open request
+        self.pending = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn both_players_have_to_ask() {
+        let mut consent = Consent::default();
+        assert_eq!(consent.ask("white"), Ok(false));
+        assert_eq!(consent.ask("white"), Err(ConsentError::AlreadyAsked));
+        assert_eq!(consent.ask("black"), Ok(true));
+        assert_eq!(consent.ask("black"), Ok(false));
+        consent.clear();
+        assert_eq!(consent.ask("black"), Ok(false));
+    }
+}
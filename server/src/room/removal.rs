@@ -0,0 +1,96 @@
+use chess::{Color, Piece};
+use serde::{Deserialize, Serialize};
+
+use crate::memory::{board::MatchedTiles, tile::Card};
+
+// Right to remove one piece from the chess board, earned by a memory match
+// Piece holds the card of the matched pair
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum RemovalEntitlement {
+    Piece(Card),
+    AnyNonKing,
+}
+
+// Reasons for refusing a clear_square request
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RemovalError {
+    InvalidSquare,
+    EmptySquare,
+    NoEntitlement,
+    WrongPiece,
+    King,
+    IllegalPosition,
+    ExposesKing,
+    UnknownPiece,
+    NotOwnPiece,
+}
+
+impl RemovalEntitlement {
+    pub fn from_match(matched: &MatchedTiles) -> Option<Self> {
+        // Returns the entitlement earned by a memory match, if any
+        if matched.get_matches().is_empty() {
+            return None;
+        }
+        match matched.get_tile()? {
+            Card::Wildcard => Some(RemovalEntitlement::AnyNonKing),
+            card => Some(RemovalEntitlement::Piece(card)),
+        }
+    }
+
+    pub fn check(&self, piece: Piece, color: Color) -> Result<(), RemovalError> {
+        // Checks that the entitlement covers the given piece
+        // Kings can never be removed
+        if piece == Piece::King {
+            return Err(RemovalError::King);
+        }
+        match self {
+            RemovalEntitlement::AnyNonKing => Ok(()),
+            RemovalEntitlement::Piece(card) if Card::from_piece(piece, color) == Some(*card) => {
+                Ok(())
+            }
+            RemovalEntitlement::Piece(_) => Err(RemovalError::WrongPiece),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normal_match_allows_only_that_piece() {
+        let matched = MatchedTiles::new("bq".parse().unwrap(), vec![3, 9]);
+        let entitlement = RemovalEntitlement::from_match(&matched).unwrap();
+        assert_eq!(entitlement.check(Piece::Queen, Color::Black), Ok(()));
+        assert_eq!(
+            entitlement.check(Piece::Queen, Color::White),
+            Err(RemovalError::WrongPiece)
+        );
+        assert_eq!(
+            entitlement.check(Piece::Rook, Color::Black),
+            Err(RemovalError::WrongPiece)
+        );
+    }
+
+    #[test]
+    fn double_wildcard_allows_any_piece_but_the_king() {
+        let matched = MatchedTiles::new(Card::Wildcard, vec![0, 1]);
+        let entitlement = RemovalEntitlement::from_match(&matched).unwrap();
+        assert_eq!(entitlement, RemovalEntitlement::AnyNonKing);
+        assert_eq!(entitlement.check(Piece::Pawn, Color::White), Ok(()));
+        assert_eq!(entitlement.check(Piece::Queen, Color::Black), Ok(()));
+        assert_eq!(
+            entitlement.check(Piece::King, Color::Black),
+            Err(RemovalError::King)
+        );
+    }
+
+    #[test]
+    fn mismatch_grants_nothing() {
+        assert_eq!(
+            RemovalEntitlement::from_match(&MatchedTiles::new_empty()),
+            None
+        );
+    }
+}
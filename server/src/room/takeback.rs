@@ -0,0 +1,170 @@
+use serde::{Deserialize, Serialize};
+
+use crate::memory::board::MemoryBoard;
+
+use super::history::PositionHistory;
+
+// Everything a turn can change, as it was when the turn started
+// The player to move and the clocks are stored by chess color, which survives reconnects
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TurnSnapshot {
+    pub chess_fen: String,
+    pub memory_board: MemoryBoard,
+    pub history: PositionHistory,
+    pub turn: String,
+    pub white_time: u64,
+    pub black_time: u64,
+    pub plies: u32,
+    pub turn_count: u32,
+}
+
+// Only the last turn can be taken back, so the stack keeps the starts of the previous and current turn
+const KEPT_TURNS: usize = 2;
+
+// Undo stack of a game and the open takeback request, if any
+// The top of the stack is the start of the current turn, so taking back the last full turn
+// drops it and returns to the one below
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct Takebacks {
+    pending: Option<String>,
+    stack: Vec<TurnSnapshot>,
+}
+
+// Reasons for refusing a takeback event
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TakebackError {
+    NotInRoom,
+    GameInactive,
+    NothingToTakeBack,
+    AlreadyPending,
+    NoRequest,
+    OwnRequest,
+}
+
+impl Takebacks {
+    pub fn push(&mut self, snapshot: TurnSnapshot) {
+        // Records the start of a turn, a new turn also withdraws the open request
+        self.pending = None;
+        if self.stack.len() == KEPT_TURNS {
+            self.stack.remove(0);
+        }
+        self.stack.push(snapshot);
+    }
+
+    pub fn request(&mut self, color: &str) -> Result<(), TakebackError> {
+        // Opens a request to take back the last turn, made by the player who played it
+        if self.pending.is_some() {
+            return Err(TakebackError::AlreadyPending);
+        }
+        match self.stack.len().checked_sub(2).map(|i| &self.stack[i]) {
+            Some(last) if last.turn == color => {
+                self.pending = Some(color.to_string());
+                Ok(())
+            }
+            _ => Err(TakebackError::NothingToTakeBack),
+        }
+    }
+
+    pub fn answer(&mut self, color: &str) -> Result<(), TakebackError> {
+        // Closes the open request on behalf of the player it was made to
+        match &self.pending {
+            None => Err(TakebackError::NoRequest),
+            Some(requester) if requester == color => Err(TakebackError::OwnRequest),
+            Some(_) => {
+                self.pending = None;
+                Ok(())
+            }
+        }
+    }
+
+    pub fn undo(&mut self) -> Option<TurnSnapshot> {
+        // Drops the current turn and returns the start of the previous one, which stays on the stack
+        if self.stack.len() < 2 {
+            return None;
+        }
+        self.stack.pop();
+        self.stack.last().cloned()
+    }
+
+    pub fn get_pending(&self) -> Option<String> {
+        // Returns the color of the player whose request is open
+        self.pending.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(turn: &str, plies: u32) -> TurnSnapshot {
+        TurnSnapshot {
+            chess_fen: String::new(),
+            memory_board: MemoryBoard::new(0),
+            history: PositionHistory::default(),
+            turn: turn.to_string(),
+            white_time: 0,
+            black_time: 0,
+            plies,
+            turn_count: plies,
+        }
+    }
+
+    #[test]
+    fn only_the_player_who_just_moved_can_ask() {
+        let mut takebacks = Takebacks::default();
+        takebacks.push(snapshot("white", 0));
+        assert_eq!(
+            takebacks.request("white"),
+            Err(TakebackError::NothingToTakeBack)
+        );
+        takebacks.push(snapshot("black", 1));
+        assert_eq!(
+            takebacks.request("black"),
+            Err(TakebackError::NothingToTakeBack)
+        );
+        assert_eq!(takebacks.request("white"), Ok(()));
+        assert_eq!(
+            takebacks.request("white"),
+            Err(TakebackError::AlreadyPending)
+        );
+        assert_eq!(takebacks.answer("white"), Err(TakebackError::OwnRequest));
+        assert_eq!(takebacks.answer("black"), Ok(()));
+        assert_eq!(takebacks.answer("black"), Err(TakebackError::NoRequest));
+    }
+
+    #[test]
+    fn undo_returns_to_the_previous_turn() {
+        let mut takebacks = Takebacks::default();
+        takebacks.push(snapshot("white", 0));
+        assert_eq!(takebacks.undo(), None);
+        takebacks.push(snapshot("black", 1));
+        takebacks.push(snapshot("white", 2));
+        assert_eq!(takebacks.undo().unwrap().plies, 1);
+        assert_eq!(takebacks.undo(), None);
+    }
+
+    #[test]
+    fn a_new_turn_withdraws_the_request() {
+        let mut takebacks = Takebacks::default();
+        takebacks.push(snapshot("white", 0));
+        takebacks.push(snapshot("black", 1));
+        takebacks.request("white").unwrap();
+        takebacks.push(snapshot("white", 2));
+        assert_eq!(takebacks.get_pending(), None);
+    }
+
+    #[test]
+    fn only_the_last_turn_is_kept() {
+        let mut takebacks = Takebacks::default();
+        for plies in 0..50 {
+            takebacks.push(snapshot(
+                if plies % 2 == 0 { "white" } else { "black" },
+                plies,
+            ));
+        }
+        assert_eq!(takebacks.stack.len(), KEPT_TURNS);
+        assert_eq!(takebacks.request("white"), Ok(()));
+        assert_eq!(takebacks.undo().unwrap().plies, 48);
+    }
+}
@@ -0,0 +1,140 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use rand::{thread_rng, Rng};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// Seconds a disconnected player's token keeps their seat
+pub const SESSION_TTL: u64 = 30 * 60;
+
+// Signs and checks the session tokens that let a player back into their seat
+// A token is "<payload>.<signature>" in unpadded base64url, the payload being "<room id>:<seat>"
+// The seat is a random id stored with the player, it is useless without the server's signature
+// Account tokens are signed the same way over "account:<account id>" and never expire
+pub struct SessionSigner {
+    key: Vec<u8>,
+}
+
+// What a valid token vouches for
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub room_id: String,
+    pub seat: String,
+}
+
+impl SessionSigner {
+    pub fn new(key: Vec<u8>) -> Self {
+        Self { key }
+    }
+
+    pub fn from_env(stored_key: Vec<u8>) -> Self {
+        // Uses SESSION_SECRET if it is set, or the key kept in the account database otherwise
+        match std::env::var("SESSION_SECRET") {
+            Ok(secret) if !secret.is_empty() => Self::new(secret.into_bytes()),
+            _ => Self::new(stored_key),
+        }
+    }
+
+    pub fn issue(&self, room_id: &str, seat: &str) -> String {
+        // Returns a token for the given seat of the given room
+        self.seal(&format!("{}:{}", room_id, seat))
+    }
+
+    pub fn verify(&self, token: &str) -> Option<Session> {
+        // Returns the session of a token signed by this server, None for anything else
+        let payload = self.open(token)?;
+        let (room_id, seat) = payload.rsplit_once(':')?;
+        Some(Session {
+            room_id: room_id.to_string(),
+            seat: seat.to_string(),
+        })
+    }
+
+    pub fn issue_account(&self, account: i64) -> String {
+        // Returns the token that logs into the given account
+        self.seal(&format!("account:{}", account))
+    }
+
+    pub fn verify_account(&self, token: &str) -> Option<i64> {
+        // Returns the account of a login token signed by this server
+        self.open(token)?.strip_prefix("account:")?.parse().ok()
+    }
+
+    fn seal(&self, payload: &str) -> String {
+        format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(payload),
+            URL_SAFE_NO_PAD.encode(self.sign(payload))
+        )
+    }
+
+    fn open(&self, token: &str) -> Option<String> {
+        // Returns the payload of a token if its signature checks out
+        let (payload, signature) = token.split_once('.')?;
+        let payload = String::from_utf8(URL_SAFE_NO_PAD.decode(payload).ok()?).ok()?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        let mut mac = self.mac();
+        mac.update(payload.as_bytes());
+        mac.verify_slice(&signature).ok()?;
+        Some(payload)
+    }
+
+    fn sign(&self, payload: &str) -> Vec<u8> {
+        let mut mac = self.mac();
+        mac.update(payload.as_bytes());
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        HmacSha256::new_from_slice(&self.key).expect("HMAC takes keys of any length")
+    }
+}
+
+pub fn new_seat() -> String {
+    // Returns a fresh random seat id
+    format!("{:032x}", thread_rng().gen::<u128>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_round_trip() {
+        let signer = SessionSigner::new(b"secret".to_vec());
+        let token = signer.issue("ABCD", "seat");
+        assert_eq!(
+            signer.verify(&token),
+            Some(Session {
+                room_id: "ABCD".to_string(),
+                seat: "seat".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn tampered_or_foreign_tokens_are_rejected() {
+        let signer = SessionSigner::new(b"secret".to_vec());
+        let token = signer.issue("ABCD", "seat");
+        let (_, signature) = token.split_once('.').unwrap();
+        let forged = format!("{}.{}", URL_SAFE_NO_PAD.encode("ABCD:other"), signature);
+        assert_eq!(signer.verify(&forged), None);
+
+        let other = SessionSigner::new(b"other".to_vec());
+        assert_eq!(other.verify(&token), None);
+        assert_eq!(signer.verify("garbage"), None);
+    }
+
+    #[test]
+    fn account_tokens_are_not_seat_tokens() {
+        let signer = SessionSigner::new(b"secret".to_vec());
+        let token = signer.issue_account(7);
+        assert_eq!(signer.verify_account(&token), Some(7));
+        assert_eq!(signer.verify_account(&signer.issue("ABCD", "7")), None);
+        assert_eq!(
+            SessionSigner::new(b"other".to_vec()).verify_account(&token),
+            None
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use std::{
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rusqlite::{params, Connection};
+use tracing::error;
+
+use crate::util::database_path;
+
+// Table of the room store, one row per open room
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS rooms (
+        id TEXT PRIMARY KEY,
+        snapshot TEXT NOT NULL,
+        saved_at INTEGER NOT NULL
+    );
+";
+
+// Snapshots of the open rooms, written on every change so games survive a restart or crash
+// A snapshot is the room serialized to JSON, with the clocks saved as the time left
+pub struct RoomStore {
+    db: Mutex<Connection>,
+}
+
+impl RoomStore {
+    pub fn open(path: &str) -> rusqlite::Result<Self> {
+        Self::init(Connection::open(path)?)
+    }
+
+    pub fn from_env() -> rusqlite::Result<Self> {
+        // Rooms are kept next to the accounts, in a table of their own
+        Self::open(&database_path())
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> rusqlite::Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(db: Connection) -> rusqlite::Result<Self> {
+        db.execute_batch(SCHEMA)?;
+        Ok(Self { db: Mutex::new(db) })
+    }
+
+    pub fn save(&self, room_id: &str, snapshot: &str) -> rusqlite::Result<()> {
+        // Stores the latest snapshot of a room, replacing the previous one
+        let saved_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+        self.db.lock().unwrap().execute(
+            "INSERT INTO rooms (id, snapshot, saved_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT (id) DO UPDATE SET snapshot = ?2, saved_at = ?3",
+            params![room_id, snapshot, saved_at],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete(&self, room_id: &str) -> rusqlite::Result<()> {
+        self.db
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM rooms WHERE id = ?1", params![room_id])?;
+        Ok(())
+    }
+
+    pub fn load_all(&self) -> rusqlite::Result<Vec<(String, String)>> {
+        // Returns the id and snapshot of every stored room
+        let db = self.db.lock().unwrap();
+        let mut statement = db.prepare("SELECT id, snapshot FROM rooms")?;
+        let rooms = statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect();
+        rooms
+    }
+}
+
+// A write queued for the room store
+enum Write {
+    Save(String, String),
+    Delete(String),
+    #[cfg(test)]
+    Flush(mpsc::Sender<()>),
+}
+
+// Writes snapshots on a thread of its own, in the order they were queued
+// SQLite writes block, so they are kept off the async runtime
+pub struct SnapshotWriter {
+    queue: mpsc::Sender<Write>,
+}
+
+impl SnapshotWriter {
+    pub fn spawn(store: Arc<RoomStore>) -> Self {
+        let (queue, writes) = mpsc::channel();
+        thread::Builder::new()
+            .name("snapshots".to_string())
+            .spawn(move || {
+                // Runs until the state, and with it the queue, is dropped
+                for write in writes {
+                    match write {
+                        Write::Save(room_id, snapshot) => store
+                            .save(&room_id, &snapshot)
+                            .unwrap_or_else(|e| error!("Failed to save room {}: {}", room_id, e)),
+                        Write::Delete(room_id) => store
+                            .delete(&room_id)
+                            .unwrap_or_else(|e| error!("Failed to delete room {}: {}", room_id, e)),
+                        #[cfg(test)]
+                        Write::Flush(done) => done.send(()).unwrap_or_default(),
+                    }
+                }
+            })
+            .expect("Failed to start the snapshot writer");
+        Self { queue }
+    }
+
+    pub fn save(&self, room_id: &str, snapshot: String) {
+        // Queues the latest snapshot of a room
+        self.queue(Write::Save(room_id.to_string(), snapshot));
+    }
+
+    pub fn delete(&self, room_id: &str) {
+        // Queues the removal of a room's snapshot
+        self.queue(Write::Delete(room_id.to_string()));
+    }
+
+    #[cfg(test)]
+    pub fn flush(&self) {
+        // Waits until every write queued so far is in the store
+        let (done, flushed) = mpsc::channel();
+        self.queue(Write::Flush(done));
+        flushed.recv().unwrap();
+    }
+
+    fn queue(&self, write: Write) {
+        self.queue
+            .send(write)
+            .unwrap_or_else(|e| error!("Snapshot writer is gone: {}", e));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshots_replace_each_other() {
+        let store = RoomStore::open_in_memory().unwrap();
+        store.save("ABCDEF", "{}").unwrap();
+        store.save("ABCDEF", r#"{"turn":"p1"}"#).unwrap();
+        store.save("GHIJKL", "{}").unwrap();
+        store.delete("GHIJKL").unwrap();
+        assert_eq!(
+            store.load_all().unwrap(),
+            vec![("ABCDEF".to_string(), r#"{"turn":"p1"}"#.to_string())]
+        );
+    }
+}
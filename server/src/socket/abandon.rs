@@ -0,0 +1,100 @@
+use socketioxide::extract::SocketRef;
+use tokio::time::{sleep, Duration};
+use tracing::{error, info};
+
+use crate::{
+    room::{abandon::Departure, Room, RoomState},
+    socket::{record::record_game, state::SocketState},
+};
+
+pub fn handle_departure(
+    socket: &SocketRef,
+    state: &'static SocketState,
+    room_id: &str,
+    room: &Room,
+    departure: Departure,
+) {
+    // Tells the player who stayed what happened to their game
+    match departure {
+        Departure::NoGame => {}
+        Departure::Aborted => {
+            info!("Game in room {} aborted before any move", room_id);
+            socket
+                .to(room_id.to_string())
+                .emit("game_aborted", ())
+                .unwrap_or_else(|e| error!("Failed to emit game_aborted event: {}", e));
+        }
+        Departure::Suspended => {
+            start_abandon_timer(socket, state, room_id, room, socket.id.to_string(), false)
+        }
+    }
+}
+
+pub fn await_opponent(socket: &SocketRef, state: &'static SocketState, room_id: &str, room: &Room) {
+    // Starts the grace period of the opponent when a player is back alone in a suspended game
+    // This happens after a restart, when every player left at the same time
+    if room.get_state() != RoomState::Suspended || room.player_count() != 1 {
+        return;
+    }
+    let (p1, p2) = room.get_players();
+    let absent = [p1, p2]
+        .into_iter()
+        .flatten()
+        .find(|u| !u.is_connected())
+        .map(|u| u.get_id());
+    if let Some(absent) = absent {
+        start_abandon_timer(socket, state, room_id, room, absent, true);
+    }
+}
+
+fn start_abandon_timer(
+    socket: &SocketRef,
+    state: &'static SocketState,
+    room_id: &str,
+    room: &Room,
+    absent: String,
+    from_stayer: bool,
+) {
+    // Counts down the grace period to the player who stayed, then awards them the game
+    // The socket is either the one that left or, with from_stayer, the one that stayed
+    let Some(grace_period) = room.get_abandon_policy().grace_period else {
+        return;
+    };
+    if room.player_count() == 0 {
+        return;
+    }
+    let stayer = move |socket: &SocketRef, room_id: String| {
+        if from_stayer {
+            socket.within(room_id)
+        } else {
+            socket.to(room_id)
+        }
+    };
+    let socket = socket.clone();
+    let room_id = room_id.to_string();
+    room.set_abandon_timer(tokio::spawn(async move {
+        for left in (1..=grace_period).rev() {
+            match state.get(room_id.clone()).await {
+                Some(room) if room.get_state() == RoomState::Suspended => {}
+                _ => return,
+            }
+            stayer(&socket, room_id.clone())
+                .emit("abandon_countdown", left)
+                .unwrap_or_else(|e| error!("Failed to emit abandon_countdown event: {}", e));
+            sleep(Duration::from_secs(1)).await;
+        }
+
+        let result = match state.rooms.write().await.get_mut(&room_id) {
+            Some(room) => room.abandon(&absent).await,
+            None => None,
+        };
+        state.save(&room_id).await;
+        if let Some(result) = result {
+            info!("Player {} abandoned the game in room {}", absent, room_id);
+            stayer(&socket, room_id.clone())
+                .emit("abandoned", &result)
+                .unwrap_or_else(|e| error!("Failed to emit abandoned event: {}", e));
+            record_game(&socket, state, &room_id, &result).await;
+        }
+    }));
+}
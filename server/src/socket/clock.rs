@@ -0,0 +1,99 @@
+use socketioxide::extract::SocketRef;
+use tokio::time::{sleep, Duration};
+use tracing::{error, info};
+
+use crate::{
+    room::{Room, RoomState, RoomType},
+    socket::{record::record_game, state::SocketState},
+};
+
+pub fn start_turn_timers(
+    socket: &SocketRef,
+    state: &'static SocketState,
+    room_id: &str,
+    room: &Room,
+) {
+    // Starts every timer that runs during a turn
+    start_clock(socket, state, room_id, room);
+    start_memory_timer(socket, state, room_id, room);
+}
+
+pub fn start_clock(socket: &SocketRef, state: &'static SocketState, room_id: &str, room: &Room) {
+    // Starts the room's clock task for the player whose turn it is
+    // The task ends the game as soon as that player runs out of time, even if nobody sends an event
+    if room.get_type() == RoomType::Casual || room.get_state() != RoomState::Playing {
+        return;
+    }
+    let socket = socket.clone();
+    let room_id = room_id.to_string();
+    room.set_clock(tokio::spawn(async move {
+        // Sleep until the player to move should be out of time, then check again
+        loop {
+            let time = match state.get(room_id.clone()).await {
+                Some(room) if room.clock_running() => room.get_turn_time().await,
+                _ => return,
+            };
+            match time {
+                Some(0) => break,
+                Some(t) => sleep(Duration::from_millis(t)).await,
+                None => return,
+            }
+        }
+
+        // Flag fall, end the game and tell both players
+        let result = match state.rooms.write().await.get_mut(&room_id) {
+            Some(room) => room.flag_fall().await,
+            None => None,
+        };
+        state.save(&room_id).await;
+        if let Some(result) = result {
+            info!("Flag fell in room {}", room_id);
+            socket
+                .within(room_id.clone())
+                .emit("timeout", &result)
+                .unwrap_or_else(|e| error!("Failed to emit timeout event: {}", e));
+            record_game(&socket, state, &room_id, &result).await;
+        }
+    }));
+}
+
+pub fn start_memory_timer(
+    socket: &SocketRef,
+    state: &'static SocketState,
+    room_id: &str,
+    room: &Room,
+) {
+    // Starts the memory phase timer of the current turn, if the room has one
+    // When it runs out, pending tiles are turned back down and the player has to move a chess piece
+    let (Some(time), Some(player)) = (room.get_memory_time(), room.get_turn()) else {
+        return;
+    };
+    if room.get_state() != RoomState::Playing {
+        return;
+    }
+    let socket = socket.clone();
+    let room_id = room_id.to_string();
+    room.set_memory_timer(tokio::spawn(async move {
+        sleep(Duration::from_secs(time)).await;
+
+        // The timer is stopped when the turn ends, but check the turn anyway
+        let tiles = match state.rooms.write().await.get_mut(&room_id) {
+            Some(room) if room.get_turn().as_ref() == Some(&player) => room.expire_memory_phase(),
+            _ => None,
+        };
+        state.save(&room_id).await;
+        if let Some(tiles) = tiles {
+            info!("Memory phase ran out for {} in room {}", player, room_id);
+            if !tiles.is_empty() {
+                socket
+                    .within(room_id.clone())
+                    .emit("unflip_tiles", tiles)
+                    .unwrap_or_else(|e| error!("Failed to emit unflip_tiles event: {}", e));
+            }
+            socket
+                .within(room_id)
+                .emit("memory_time_up", player)
+                .unwrap_or_else(|e| error!("Failed to emit memory_time_up event: {}", e));
+        }
+    }));
+}
@@ -0,0 +1,181 @@
+use socketioxide::extract::{Data, SocketRef, State};
+use tracing::{error, info};
+
+use crate::{
+    accounts::{Account, AccountDetails, AccountId, DEFAULT_PAGE_SIZE},
+    rating::RatingPool,
+    socket::state::SocketState,
+    util::get_account_from_extension,
+};
+
+// Struct to represent player_profile params, no id asks for the sender's own profile
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ProfileRequest {
+    #[serde(default)]
+    id: Option<i64>,
+}
+
+// Struct to represent leaderboard params
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct LeaderboardRequest {
+    pool: RatingPool,
+    limit: Option<u32>,
+}
+
+// Struct to represent player_games params, no id asks for the sender's own games
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct GamesRequest {
+    #[serde(default)]
+    id: Option<i64>,
+    #[serde(default)]
+    page: u32,
+    per_page: Option<u32>,
+}
+
+pub async fn on_register(
+    socket: SocketRef,
+    state: State<SocketState>,
+    Data::<AccountDetails>(details): Data<AccountDetails>,
+) {
+    // Creates an account and logs the socket into it
+    if details.name.trim().is_empty() {
+        account_failed(&socket, "Please enter a username");
+        return;
+    }
+    match state.accounts.create(details) {
+        Ok(account) => {
+            info!("Player {} registered account {}", socket.id, account.id);
+            logged_in(&socket, &state, account);
+        }
+        Err(e) => {
+            error!("Failed to create account: {}", e);
+            account_failed(&socket, "Could not create the account");
+        }
+    }
+}
+
+pub async fn on_login(
+    socket: SocketRef,
+    state: State<SocketState>,
+    Data::<String>(token): Data<String>,
+) {
+    // Logs the socket into the account a token was issued for
+    let account = state
+        .sessions
+        .verify_account(&token)
+        .map(|id| state.accounts.get(id));
+    match account {
+        Some(Ok(Some(account))) => logged_in(&socket, &state, account),
+        Some(Err(e)) => {
+            error!("Failed to load account: {}", e);
+            account_failed(&socket, "Could not load the account");
+        }
+        _ => account_failed(&socket, "Unknown account"),
+    }
+}
+
+pub async fn on_update_account(
+    socket: SocketRef,
+    state: State<SocketState>,
+    Data::<AccountDetails>(details): Data<AccountDetails>,
+) {
+    // Changes the name and avatar of the sender's account
+    let Some(id) = get_account_from_extension(&socket) else {
+        account_failed(&socket, "Not logged in");
+        return;
+    };
+    if details.name.trim().is_empty() {
+        account_failed(&socket, "Please enter a username");
+        return;
+    }
+    match state.accounts.update(id, details) {
+        Ok(Some(account)) => logged_in(&socket, &state, account),
+        Ok(None) => account_failed(&socket, "Unknown account"),
+        Err(e) => {
+            error!("Failed to update account: {}", e);
+            account_failed(&socket, "Could not update the account");
+        }
+    }
+}
+
+pub async fn on_player_profile(
+    socket: SocketRef,
+    state: State<SocketState>,
+    Data::<ProfileRequest>(request): Data<ProfileRequest>,
+) {
+    // Sends the profile and lifetime stats of an account, or nothing if there is none
+    let profile = request
+        .id
+        .or_else(|| get_account_from_extension(&socket))
+        .and_then(|id| {
+            state.accounts.get_profile(id).unwrap_or_else(|e| {
+                error!("Failed to load profile: {}", e);
+                None
+            })
+        });
+    socket
+        .emit("player_profile", profile)
+        .unwrap_or_else(|e| error!("Failed to emit player_profile event: {}", e));
+}
+
+pub async fn on_leaderboard(
+    socket: SocketRef,
+    state: State<SocketState>,
+    Data::<LeaderboardRequest>(request): Data<LeaderboardRequest>,
+) {
+    // Sends the highest rated players of a pool
+    let limit = request.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    let entries = state
+        .accounts
+        .leaderboard(request.pool, limit)
+        .unwrap_or_else(|e| {
+            error!("Failed to load leaderboard: {}", e);
+            vec![]
+        });
+    socket
+        .emit("leaderboard", (request.pool, entries))
+        .unwrap_or_else(|e| error!("Failed to emit leaderboard event: {}", e));
+}
+
+pub async fn on_player_games(
+    socket: SocketRef,
+    state: State<SocketState>,
+    Data::<GamesRequest>(request): Data<GamesRequest>,
+) {
+    // Sends a page of an account's game history, or nothing if there is no account
+    let Some(id) = request.id.or_else(|| get_account_from_extension(&socket)) else {
+        socket
+            .emit("player_games", ())
+            .unwrap_or_else(|e| error!("Failed to emit player_games event: {}", e));
+        return;
+    };
+    let per_page = request.per_page.unwrap_or(DEFAULT_PAGE_SIZE);
+    let games = state
+        .accounts
+        .player_games(id, request.page, per_page)
+        .map_err(|e| error!("Failed to load games of account {}: {}", id, e))
+        .ok();
+    socket
+        .emit("player_games", games)
+        .unwrap_or_else(|e| error!("Failed to emit player_games event: {}", e));
+}
+
+pub async fn on_logout(socket: SocketRef) {
+    // Goes back to anonymous play, rooms joined from now on aren't linked to the account
+    socket.extensions.remove::<AccountId>();
+}
+
+fn logged_in(socket: &SocketRef, state: &SocketState, account: Account) {
+    // Remembers the account for this socket and sends it back with its login token
+    socket.extensions.insert(AccountId(account.id));
+    let token = state.sessions.issue_account(account.id);
+    socket
+        .emit("account", (account, token))
+        .unwrap_or_else(|e| error!("Failed to emit account event: {}", e));
+}
+
+fn account_failed(socket: &SocketRef, reason: &str) {
+    socket
+        .emit("account_failed", reason)
+        .unwrap_or_else(|e| error!("Failed to emit account_failed event: {}", e));
+}
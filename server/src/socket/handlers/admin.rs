@@ -0,0 +1,56 @@
+use socketioxide::extract::{Data, SocketRef, State};
+use tracing::{error, info, warn};
+
+use crate::{socket::state::SocketState, util::is_admin};
+
+// Struct to represent audit_room params
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct AuditRoom {
+    token: String,
+    room_id: String,
+    repair: bool,
+}
+
+pub async fn on_audit_room(
+    socket: SocketRef,
+    state: State<SocketState>,
+    Data::<AuditRoom>(data): Data<AuditRoom>,
+) {
+    if !is_admin(&data.token) {
+        warn!("Rejected audit_room from {}", socket.id);
+        socket
+            .emit("admin_denied", "audit_room")
+            .unwrap_or_else(|e| error!("Failed to emit admin_denied event: {}", e));
+        return;
+    }
+
+    let room_id = data.room_id;
+    if let Some(room) = state.rooms.write().await.get_mut(&room_id) {
+        let report = if data.repair {
+            room.reconcile_memory(&room_id)
+        } else {
+            room.audit_memory(&room_id)
+        };
+        info!(
+            "Audited memory board of room {}, consistent: {}",
+            room_id,
+            report.is_consistent()
+        );
+
+        // Let the players drop the repaired tiles from their boards
+        if !report.removed.is_empty() {
+            socket
+                .within(room_id.clone())
+                .emit("remove_tiles", report.removed.clone())
+                .unwrap_or_else(|e| error!("Failed to emit remove_tiles event: {}", e));
+        }
+        socket
+            .emit("audit_report", report)
+            .unwrap_or_else(|e| error!("Failed to emit audit_report event: {}", e));
+    } else {
+        socket
+            .emit("audit_report", ())
+            .unwrap_or_else(|e| error!("Failed to emit audit_report event: {}", e));
+    }
+    state.save(&room_id).await;
+}
@@ -1,14 +1,20 @@
 use std::str::FromStr;
 
-use chess::{BoardBuilder, CastleRights, Color, Piece, Square};
+use chess::Square;
 use socketioxide::extract::{Data, SocketRef, State};
 use tracing::error;
 
 use crate::{
-    chess::util::{is_game_over, Move},
-    room::RoomState,
-    socket::state::SocketState,
-    util::get_data_from_extension,
+    chess::util::{count_pieces, is_game_over, remove_piece, Move},
+    memory::tile::Card,
+    room::{
+        action::GameAction,
+        phase::PhaseEvent,
+        removal::{RemovalEntitlement, RemovalError},
+        MoveError,
+    },
+    socket::{clock::start_turn_timers, record::record_game, state::SocketState},
+    util::{audit_memory_board, get_data_from_extension, reject_action},
 };
 
 use super::user::on_timeout;
@@ -19,45 +25,41 @@ pub async fn on_move_piece(
     Data::<Move>(_move): Data<Move>,
 ) {
     let room_id = get_data_from_extension(&socket);
-    // Get room
-    let room = state.get(room_id.clone()).await;
-
-    // Return if no room is found
-    if room.is_none() {
+    // The move is played on the room in place, so the clock and memory timer can't overwrite it
+    let mut rooms = state.rooms.write().await;
+    let Some(room) = rooms.get_mut(&room_id) else {
+        // Return if no room is found
         error!("Room {} not found", room_id);
         return;
-    }
-    let mut room = room.unwrap();
+    };
 
     if room.timeout().await {
+        drop(rooms);
         on_timeout(socket, state).await;
         return;
     }
 
-    // If room is inactive
-    if room.get_state() != RoomState::Playing {
-        if room.get_state() == RoomState::Ready {
-            // Start the game if the room is ready
-            room.start_game(socket.id.clone().to_string()).await;
-            socket
-                .within(room_id.clone())
-                .emit("white", socket.id.to_string())
-                .unwrap_or_else(|e| error!("Failed to emit game_started event: {}", e));
-        } else {
-            error!("Received move_piece event for non-playing room {}", room_id);
-            return;
-        }
+    // Only the player whose turn it is can move
+    if let Err(rejection) = room.authorize(socket.id.as_str(), GameAction::MovePiece) {
+        reject_action(&socket, rejection);
+        return;
     }
 
-    // Reset memory board flips
-    let mut memory_board = room.get_memory_board();
-    memory_board.reset_flips();
-    room.set_memory_board(memory_board);
+    // Parse given move
+    let Some(chess_move) = _move.to_chess_move() else {
+        // Emit error if move is invalid
+        error!("Invalid move: {:?} in room {}", _move, room_id);
+        socket
+            .emit("invalid_move", "")
+            .unwrap_or_else(|e| error!("Failed to emit invalid_move event: {}", e));
+        return;
+    };
+    let from = chess_move.get_source();
 
-    // Get Game Board
-    let board = match room.get_chess_board() {
+    // Check if move is legal, the game starts with the first legal move of a ready room
+    let board = match room.validate_move(chess_move).await {
         Ok(board) => board,
-        Err(_) => {
+        Err(MoveError::IllegalFen) => {
             error!("Invalid chess board in room {}", room_id);
             // Emit Error if chess fen is illegal/corrupt
             socket
@@ -66,110 +68,57 @@ pub async fn on_move_piece(
                 .unwrap_or_else(|e| error!("Failed to emit illegal_fen event: {}", e));
             return;
         }
+        Err(MoveError::IllegalMove) => {
+            error!("Illegal move: {:?} in room {}", chess_move, room_id);
+            socket
+                .emit("illegal_move", _move)
+                .unwrap_or_else(|e| error!("Failed to emit illegal_move event: {}", e));
+            return;
+        }
     };
 
-    // Parse given move
-    let from = Square::from_str(&_move.from);
-    let to = Square::from_str(&_move.to);
-    let promotion = match _move.promotion.as_str() {
-        "q" => Some(Piece::Queen),
-        "r" => Some(Piece::Rook),
-        "b" => Some(Piece::Bishop),
-        "n" => Some(Piece::Knight),
-        _ => None,
-    };
-    if from.is_err() || to.is_err() {
-        // Emit error if move is invalid
-        error!("Invalid move: {:?} in room {}", _move, room_id);
-        socket
-            .emit("invalid_move", "")
-            .unwrap_or_else(|e| error!("Failed to emit invalid_move event: {}", e));
-        return;
-    }
-    let from = from.unwrap();
-    let to = to.unwrap();
+    // Reset memory board flips
+    let mut memory_board = room.get_memory_board();
+    memory_board.reset_flips();
+    room.set_memory_board(memory_board);
+
     let piece = board
         .piece_on(from)
         .unwrap()
         .to_string(board.color_on(from).unwrap());
 
-    let chess_move = chess::ChessMove::new(from, to, promotion);
-
-    // Check if move is legal
-    if !board.legal(chess_move) {
-        error!("Illegal move: {:?} in room {}", chess_move, room_id);
-        socket
-            .emit("illegal_move", _move)
-            .unwrap_or_else(|e| error!("Failed to emit illegal_move event: {}", e));
-        return;
-    }
-
-    // Check if the move is a capture
-    let dest = chess_move.get_dest();
-    let captured_piece = board
-        .piece_on(dest)
-        .map(|p| p.to_string(board.color_on(dest).unwrap()));
-    if let Some(capture) = captured_piece.clone() {
-        // Capture
-        let capture = match capture.as_str() {
-            "p" => "bp",
-            "q" => "bq",
-            "r" => "br",
-            "b" => "bb",
-            "n" => "bn",
-            "k" => "bk",
-            "P" => "wp",
-            "Q" => "wq",
-            "R" => "wr",
-            "B" => "wb",
-            "N" => "wn",
-            "K" => "wk",
-            _ => "",
-        };
-        // Remove corresponding piece from memory board
-        let mut memory_board = room.get_memory_board();
-        let removed = memory_board.remove_tiles(capture.to_string());
-        room.set_memory_board(memory_board);
+    // Play the move, captures (en passant included) and promotions update the memory board
+    let effects = room.play_move(&board, chess_move);
+    let captured_piece = effects
+        .captured
+        .map(|(piece, color)| piece.to_string(color));
+    if captured_piece.is_some() {
         socket
             .within(room_id.clone())
-            .emit("remove_tiles", removed)
-            .unwrap_or_else(|e| error!("Failed to emit emove_tiles event: {}", e));
+            .emit("remove_tiles", effects.removed_tiles)
+            .unwrap_or_else(|e| error!("Failed to emit remove_tiles event: {}", e));
     }
-
-    // Check if move is a pawn promotion
-    if promotion.is_some() {
-        // Add corresponding piece to memory board
-        let mut memory_board = room.get_memory_board();
-        let tiles = memory_board.upgrade_tile(
-            promotion.unwrap().to_string(board.side_to_move()),
-            board.side_to_move(),
-        );
-        if let Some(t) = tiles {
-            println!("{:?}", memory_board);
-            room.set_memory_board(memory_board);
-            socket
-                .within(room_id.clone())
-                .emit("upgrade_tile", t)
-                .unwrap_or_else(|e| error!("Failed to emit add_tile event: {}", e));
-        }
-    }
-
-    // Set the new board
-    let new_board = board.make_move_new(chess_move);
-    room.set_chess_board(new_board);
+    audit_memory_board(&room_id, room);
 
     // Check for game end
-    if let Some(result) = is_game_over(&new_board, &room) {
+    let new_board = board.make_move_new(chess_move);
+    if let Some(result) = is_game_over(&new_board, room) {
         socket
             .within(room_id.clone())
-            .emit(result.result.clone(), result)
+            .emit(result.result.clone(), &result)
             .unwrap_or_else(|e| error!("Failed to emit event: {}", e));
-        room.end_game();
-        state.update(room_id.clone(), room).await;
-        return;
+        room.end_game(&result).await;
+        drop(rooms);
+        state.save(&room_id).await;
+        record_game(&socket, &state, &room_id, &result).await;
     } else {
+        let draw_offer = room.get_draw_offer();
+        let takeback_request = room.get_takeback_request();
         room.switch_turn().await;
-        state.update(room_id.clone(), room.clone()).await;
+        start_turn_timers(&socket, state.0, &room_id, room);
+        let room = room.clone();
+        drop(rooms);
+        state.save(&room_id).await;
         // Emit the move to the opponent
         socket
             .within(room_id.clone())
@@ -183,6 +132,20 @@ pub async fn on_move_piece(
                 ),
             )
             .unwrap_or_else(|e| error!("Failed to emit piece_moved event: {}", e));
+        // Moving withdraws the mover's draw offer
+        if draw_offer.is_some() && room.get_draw_offer().is_none() {
+            socket
+                .within(room_id.clone())
+                .emit("draw_expired", ())
+                .unwrap_or_else(|e| error!("Failed to emit draw_expired event: {}", e));
+        }
+        // A request to take back the previous turn is dropped once the opponent moves
+        if takeback_request.is_some() {
+            socket
+                .within(room_id.clone())
+                .emit("takeback_expired", ())
+                .unwrap_or_else(|e| error!("Failed to emit takeback_expired event: {}", e));
+        }
         // Emit turn event
         let turn = room.get_turn();
         let times = room.get_player_times().await;
@@ -201,15 +164,19 @@ pub async fn on_clear_square(
     Data::<String>(square): Data<String>,
 ) {
     let room_id = get_data_from_extension(&socket);
-    // Get room
-    let room = state.get(room_id.clone()).await;
-
-    // Return if no room is found
-    if room.is_none() {
+    // The square is cleared on the room in place, like a move
+    let mut rooms = state.rooms.write().await;
+    let Some(room) = rooms.get_mut(&room_id) else {
+        // Return if no room is found
         error!("Room {} not found", room_id);
         return;
+    };
+
+    // Only the player whose turn it is can clear a square
+    if let Err(rejection) = room.authorize(socket.id.as_str(), GameAction::ClearSquare) {
+        reject_action(&socket, rejection);
+        return;
     }
-    let mut room = room.unwrap();
 
     // Get Game Board
     let board = match room.get_chess_board() {
@@ -224,79 +191,67 @@ pub async fn on_clear_square(
         }
     };
 
-    let board_square = Square::from_str(&square).unwrap();
-    let color = board.color_on(board_square).unwrap();
-    let piece = board.piece_on(board_square).unwrap();
-    let piece_str = piece.clone().to_string(color.clone());
-
-    // Convert to BoardBuilder
-    let mut builder = BoardBuilder::from(board);
-
-    // Check if piece was a rook
-    if Piece::Rook == piece {
-        // Cehck if rook is kingside or queenside
-        if color == Color::Black {
-            let mut castle_rights = builder.get_castle_rights(color);
-            if square == "a8" {
-                if castle_rights == CastleRights::Both {
-                    castle_rights = CastleRights::KingSide;
-                } else {
-                    castle_rights = CastleRights::NoRights;
-                }
-            } else if square == "h8" {
-                if castle_rights == CastleRights::Both {
-                    castle_rights = CastleRights::QueenSide;
-                } else {
-                    castle_rights = CastleRights::NoRights;
-                }
-            }
-            builder.castle_rights(color, castle_rights);
-        } else {
-            let mut castle_rights = builder.get_castle_rights(color);
-            if square == "a1" {
-                if castle_rights == CastleRights::Both {
-                    castle_rights = CastleRights::KingSide;
-                } else {
-                    castle_rights = CastleRights::NoRights;
-                }
-            } else if square == "h1" {
-                if castle_rights == CastleRights::Both {
-                    castle_rights = CastleRights::QueenSide;
-                } else {
-                    castle_rights = CastleRights::NoRights;
-                }
-            }
-            builder.castle_rights(color, castle_rights);
+    // Find the piece on the given square
+    let board_square = match Square::from_str(&square) {
+        Ok(sq) => sq,
+        Err(_) => {
+            clear_failed(&socket, RemovalError::InvalidSquare);
+            return;
         }
-    }
-
-    println!("{:?}", builder.get_castle_rights(color));
-    // Remove corresponding piece from chess board
-    builder.clear_square(board_square);
+    };
+    let (piece, color) = match (board.piece_on(board_square), board.color_on(board_square)) {
+        (Some(piece), Some(color)) => (piece, color),
+        _ => {
+            clear_failed(&socket, RemovalError::EmptySquare);
+            return;
+        }
+    };
+    let piece_str = piece.to_string(color);
 
-    // Convert back to board
-    let new_board: Result<chess::Board, _> = builder.try_into();
-    if new_board.is_err() {
-        socket
-            .emit("clear_failed", "Invalid fen")
-            .unwrap_or_else(|e| error!("Failed to emit clear_failed event: {}", e));
+    // Check that the last memory match allows removing this piece
+    if let Err(e) = room.check_removal(piece, color) {
+        clear_failed(&socket, e);
         return;
     }
 
-    let new_board = new_board.unwrap();
+    // Remove the piece, as long as the position stays legal
+    let new_board = match remove_piece(&board, board_square) {
+        Ok(new_board) => new_board,
+        Err(e) => {
+            clear_failed(&socket, e);
+            return;
+        }
+    };
     room.set_chess_board(new_board);
+    room.advance_phase(PhaseEvent::SquareCleared);
 
-    state.update(room_id.clone(), room.clone()).await;
+    // A double wildcard match didn't use up the removed piece's tiles, so remove them now
+    let card = Card::from_piece(piece, color);
+    if let (Some(RemovalEntitlement::AnyNonKing), Some(card)) = (room.consume_removal(), card) {
+        let removed = room
+            .get_mut_memory_board()
+            .remove_surplus(card, count_pieces(&new_board, piece, color));
+        socket
+            .within(room_id.clone())
+            .emit("remove_tiles", removed)
+            .unwrap_or_else(|e| error!("Failed to emit remove_tiles event: {}", e));
+    }
+    audit_memory_board(&room_id, room);
 
     // Check for game end
-    if let Some(result) = is_game_over(&new_board, &room) {
+    if let Some(result) = is_game_over(&new_board, room) {
         socket
             .within(room_id.clone())
-            .emit(result.result.clone(), result)
+            .emit(result.result.clone(), &result)
             .unwrap_or_else(|e| error!("Failed to emit event: {}", e));
-        room.end_game();
+        room.end_game(&result).await;
+        drop(rooms);
+        state.save(&room_id).await;
+        record_game(&socket, &state, &room_id, &result).await;
         return;
     }
+    drop(rooms);
+    state.save(&room_id).await;
 
     socket
         .within(room_id.clone())
@@ -304,6 +259,13 @@ pub async fn on_clear_square(
         .unwrap_or_else(|e| error!("Failed to emit square_cleared event: {}", e));
 }
 
+fn clear_failed(socket: &SocketRef, reason: RemovalError) {
+    // Tells the sender why their piece removal was refused
+    socket
+        .emit("clear_failed", reason)
+        .unwrap_or_else(|e| error!("Failed to emit clear_failed event: {}", e));
+}
+
 pub async fn on_get_chess_board(socket: SocketRef, state: State<SocketState>) {
     let room_id = get_data_from_extension(&socket);
     if !room_id.is_empty() {
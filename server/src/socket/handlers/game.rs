@@ -1,25 +1,260 @@
 use socketioxide::extract::{SocketRef, State};
-use tracing::error;
+use tracing::{error, info};
 
-use crate::{socket::state::SocketState, util::get_data_from_extension};
+use crate::{
+    room::{draw::DrawError, rematch::ConsentError, takeback::TakebackError, Room},
+    socket::{clock::start_turn_timers, record::record_game, state::SocketState},
+    util::{audit_memory_board, get_data_from_extension},
+};
 
 pub async fn on_reset_game(socket: SocketRef, state: State<SocketState>) {
-    // Reset the game state
+    // Resets the game, a game in progress needs both players to ask
     let room_id = get_data_from_extension(&socket);
-    if let Some(mut room) = state.get(room_id.clone()).await {
-        room.reset_game().await;
-        state.update(room_id.clone(), room.clone()).await;
+    let reset = match state.rooms.write().await.get_mut(&room_id) {
+        Some(room) => match room.request_reset(socket.id.as_str()).await {
+            Ok(true) => Ok(Some(room.clone())),
+            Ok(false) => Ok(None),
+            Err(e) => Err(e),
+        },
+        None => Err(ConsentError::NotInRoom),
+    };
+    state.save(&room_id).await;
+    match reset {
+        Ok(Some(room)) => {
+            info!("Reset room {} with seed {}", room_id, room.get_seed());
+            emit_game_reset(&socket, &room_id, &room);
+        }
+        Ok(None) => socket
+            .within(room_id)
+            .emit("reset_requested", socket.id.to_string())
+            .unwrap_or_else(|e| error!("Failed to emit reset_requested event: {}", e)),
+        Err(e) => consent_failed(&socket, "reset_failed", e),
+    }
+}
+
+pub async fn on_rematch(socket: SocketRef, state: State<SocketState>) {
+    // Asks for a rematch, the next game starts once both players asked
+    let room_id = get_data_from_extension(&socket);
+    let rematch = match state.rooms.write().await.get_mut(&room_id) {
+        Some(room) => match room.request_rematch(socket.id.as_str()).await {
+            Ok(true) => Ok(Some(room.clone())),
+            Ok(false) => Ok(None),
+            Err(e) => Err(e),
+        },
+        None => Err(ConsentError::NotInRoom),
+    };
+    state.save(&room_id).await;
+    match rematch {
+        Ok(Some(room)) => {
+            info!(
+                "Rematch in room {} after {} games with seed {}",
+                room_id,
+                room.get_score().games(),
+                room.get_seed()
+            );
+            emit_game_reset(&socket, &room_id, &room);
+        }
+        Ok(None) => socket
+            .within(room_id)
+            .emit("rematch_requested", socket.id.to_string())
+            .unwrap_or_else(|e| error!("Failed to emit rematch_requested event: {}", e)),
+        Err(e) => consent_failed(&socket, "rematch_failed", e),
+    }
+}
+
+fn emit_game_reset(socket: &SocketRef, room_id: &str, room: &Room) {
+    // Sends both players the fresh game, its colors and the match score
+    audit_memory_board(room_id, room);
+    socket
+        .within(room_id.to_string())
+        .emit("game_reset", room.get_score())
+        .unwrap_or_else(|e| {
+            error!("Error sending game_reset event: {:?}", e);
+        });
+    socket
+        .within(room_id.to_string())
+        .emit("memory_board", room.get_memory_board().redacted())
+        .unwrap_or_else(|e| {
+            error!("Error sending memory_board event: {:?}", e);
+        });
+    if let Some(white) = room.get_turn() {
         socket
-            .within(room_id.clone())
-            .emit("game_reset", ())
-            .unwrap_or_else(|e| {
-                error!("Error sending game_reset event: {:?}", e);
-            });
+            .within(room_id.to_string())
+            .emit("white", white)
+            .unwrap_or_else(|e| error!("Failed to emit white event: {}", e));
+    }
+}
+
+fn consent_failed(socket: &SocketRef, event: &'static str, reason: ConsentError) {
+    // Tells the sender why their rematch or reset request was refused
+    socket
+        .emit(event, reason)
+        .unwrap_or_else(|e| error!("Failed to emit {} event: {}", event, e));
+}
+
+pub async fn on_resign(socket: SocketRef, state: State<SocketState>) {
+    // Ends the game with a win for the sender's opponent
+    let room_id = get_data_from_extension(&socket);
+    let result = match state.rooms.write().await.get_mut(&room_id) {
+        Some(room) => room.resign(socket.id.as_str()).await,
+        None => Err(DrawError::NotInRoom),
+    };
+    state.save(&room_id).await;
+    match result {
+        Ok(result) => {
+            info!("{} resigned in room {}", socket.id, room_id);
+            socket
+                .within(room_id.clone())
+                .emit(result.result.clone(), &result)
+                .unwrap_or_else(|e| error!("Failed to emit resignation event: {}", e));
+            record_game(&socket, &state, &room_id, &result).await;
+        }
+        Err(e) => draw_failed(&socket, e),
+    }
+}
+
+pub async fn on_offer_draw(socket: SocketRef, state: State<SocketState>) {
+    // Offers the sender's opponent a draw
+    let room_id = get_data_from_extension(&socket);
+    let offered = match state.rooms.write().await.get_mut(&room_id) {
+        Some(room) => room.offer_draw(socket.id.as_str()),
+        None => Err(DrawError::NotInRoom),
+    };
+    state.save(&room_id).await;
+    match offered {
+        Ok(()) => socket
+            .within(room_id)
+            .emit("draw_offered", socket.id.to_string())
+            .unwrap_or_else(|e| error!("Failed to emit draw_offered event: {}", e)),
+        Err(e) => draw_failed(&socket, e),
+    }
+}
+
+pub async fn on_accept_draw(socket: SocketRef, state: State<SocketState>) {
+    // Ends the game in a draw if the opponent offered one
+    let room_id = get_data_from_extension(&socket);
+    let result = match state.rooms.write().await.get_mut(&room_id) {
+        Some(room) => room.accept_draw(socket.id.as_str()).await,
+        None => Err(DrawError::NotInRoom),
+    };
+    state.save(&room_id).await;
+    match result {
+        Ok(result) => {
+            info!("Draw agreed in room {}", room_id);
+            socket
+                .within(room_id.clone())
+                .emit(result.result.clone(), &result)
+                .unwrap_or_else(|e| error!("Failed to emit draw_agreed event: {}", e));
+            record_game(&socket, &state, &room_id, &result).await;
+        }
+        Err(e) => draw_failed(&socket, e),
+    }
+}
+
+pub async fn on_decline_draw(socket: SocketRef, state: State<SocketState>) {
+    // Turns down the opponent's draw offer
+    let room_id = get_data_from_extension(&socket);
+    let declined = match state.rooms.write().await.get_mut(&room_id) {
+        Some(room) => room.decline_draw(socket.id.as_str()),
+        None => Err(DrawError::NotInRoom),
+    };
+    state.save(&room_id).await;
+    match declined {
+        Ok(()) => socket
+            .within(room_id)
+            .emit("draw_declined", socket.id.to_string())
+            .unwrap_or_else(|e| error!("Failed to emit draw_declined event: {}", e)),
+        Err(e) => draw_failed(&socket, e),
+    }
+}
+
+fn draw_failed(socket: &SocketRef, reason: DrawError) {
+    // Tells the sender why their resignation or draw event was refused
+    socket
+        .emit("draw_failed", reason)
+        .unwrap_or_else(|e| error!("Failed to emit draw_failed event: {}", e));
+}
+
+pub async fn on_request_takeback(socket: SocketRef, state: State<SocketState>) {
+    // Asks the opponent to take back the sender's last turn
+    let room_id = get_data_from_extension(&socket);
+    let requested = match state.rooms.write().await.get_mut(&room_id) {
+        Some(room) => room.request_takeback(socket.id.as_str()),
+        None => Err(TakebackError::NotInRoom),
+    };
+    state.save(&room_id).await;
+    match requested {
+        Ok(()) => socket
+            .within(room_id)
+            .emit("takeback_requested", socket.id.to_string())
+            .unwrap_or_else(|e| error!("Failed to emit takeback_requested event: {}", e)),
+        Err(e) => takeback_failed(&socket, e),
+    }
+}
+
+pub async fn on_accept_takeback(socket: SocketRef, state: State<SocketState>) {
+    // Rolls the last turn back and sends both players the restored game
+    let room_id = get_data_from_extension(&socket);
+    let room = {
+        let mut rooms = state.rooms.write().await;
+        let Some(room) = rooms.get_mut(&room_id) else {
+            takeback_failed(&socket, TakebackError::NotInRoom);
+            return;
+        };
+        if let Err(e) = room.accept_takeback(socket.id.as_str()).await {
+            takeback_failed(&socket, e);
+            return;
+        }
+        audit_memory_board(&room_id, room);
+        start_turn_timers(&socket, state.0, &room_id, room);
+        room.clone()
+    };
+    state.save(&room_id).await;
+    info!("Took back a turn in room {}", room_id);
+
+    socket
+        .within(room_id.clone())
+        .emit("takeback_accepted", ())
+        .unwrap_or_else(|e| error!("Failed to emit takeback_accepted event: {}", e));
+    if let Ok(board) = room.get_chess_board() {
         socket
             .within(room_id.clone())
-            .emit("memory_board", room.get_memory_board())
-            .unwrap_or_else(|e| {
-                error!("Error sending memory_board event: {:?}", e);
-            });
+            .emit("chess_board", board.to_string())
+            .unwrap_or_else(|e| error!("Failed to emit chess_board event: {}", e));
     }
+    socket
+        .within(room_id.clone())
+        .emit("memory_board", room.get_memory_board().redacted())
+        .unwrap_or_else(|e| error!("Failed to emit memory_board event: {}", e));
+    let times = room.get_player_times().await;
+    if let Some(turn) = room.get_turn() {
+        socket
+            .within(room_id)
+            .emit("turn", (turn, times))
+            .unwrap_or_else(|e| error!("Failed to emit turn event: {}", e));
+    }
+}
+
+pub async fn on_decline_takeback(socket: SocketRef, state: State<SocketState>) {
+    // Turns down the opponent's takeback request
+    let room_id = get_data_from_extension(&socket);
+    let declined = match state.rooms.write().await.get_mut(&room_id) {
+        Some(room) => room.decline_takeback(socket.id.as_str()),
+        None => Err(TakebackError::NotInRoom),
+    };
+    state.save(&room_id).await;
+    match declined {
+        Ok(()) => socket
+            .within(room_id)
+            .emit("takeback_declined", socket.id.to_string())
+            .unwrap_or_else(|e| error!("Failed to emit takeback_declined event: {}", e)),
+        Err(e) => takeback_failed(&socket, e),
+    }
+}
+
+fn takeback_failed(socket: &SocketRef, reason: TakebackError) {
+    // Tells the sender why their takeback event was refused
+    socket
+        .emit("takeback_failed", reason)
+        .unwrap_or_else(|e| error!("Failed to emit takeback_failed event: {}", e));
 }
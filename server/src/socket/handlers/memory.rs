@@ -2,8 +2,10 @@ use socketioxide::extract::{Data, SocketRef, State};
 use tracing::error;
 
 use crate::{
-    memory::board::MatchedTiles, room::RoomState, socket::state::SocketState,
-    util::get_data_from_extension,
+    chess::util::legal_removals,
+    room::{action::GameAction, phase::PhaseEvent, removal::RemovalEntitlement, RoomState},
+    socket::{clock::start_turn_timers, state::SocketState},
+    util::{audit_memory_board, get_data_from_extension, reject_action},
 };
 
 pub async fn on_flip_tile(
@@ -14,24 +16,21 @@ pub async fn on_flip_tile(
     let room_id = get_data_from_extension(&socket);
     // Get write access to the room
     if let Some(room) = state.rooms.write().await.get_mut(&room_id) {
-        if room.get_state() != RoomState::Playing {
-            if room.get_state() == RoomState::Ready {
-                room.start_game(socket.id.to_string()).await;
+        // Only the player whose turn it is can flip tiles
+        if let Err(rejection) = room.authorize(socket.id.as_str(), GameAction::FlipTile) {
+            reject_action(&socket, rejection);
+            return;
+        }
+        if room.get_state() == RoomState::Ready {
+            room.start_game().await;
+            start_turn_timers(&socket, state.0, &room_id, room);
+            let turn = room.get_turn();
+            let times = room.get_player_times().await;
+            if let Some(turn) = turn {
                 socket
                     .within(room_id.clone())
-                    .emit("white", socket.id.to_string())
-                    .unwrap_or_else(|e| error!("Failed to emit game_started event: {}", e));
-                let turn = room.get_turn();
-                let times = room.get_player_times().await;
-                if let Some(turn) = turn {
-                    socket
-                        .within(room_id.clone())
-                        .emit("turn", (turn, times))
-                        .unwrap_or_else(|e| error!("Failed to emit turn event: {}", e));
-                }
-            } else {
-                error!("Received flip_tile event for non-playing room {}", room_id);
-                return;
+                    .emit("turn", (turn, times))
+                    .unwrap_or_else(|e| error!("Failed to emit turn event: {}", e));
             }
         }
         let board = room.get_mut_memory_board();
@@ -42,46 +41,59 @@ pub async fn on_flip_tile(
                 .emit("tile_flipped", (index, tile, socket.id.to_string()))
                 .unwrap_or_else(|e| error!("Failed to emit tile_flipped event: {}", e));
             room.increment_turns();
+            room.advance_phase(PhaseEvent::TileFlipped);
+            audit_memory_board(&room_id, room);
         }
     }
+    state.save(&room_id).await;
 }
 
 pub async fn on_match_tiles(socket: SocketRef, state: State<SocketState>) {
     let room_id = get_data_from_extension(&socket);
     // Get write access to the room
     if let Some(room) = state.rooms.write().await.get_mut(&room_id) {
-        if room.get_state() != RoomState::Playing {
-            error!(
-                "Received match_tiles event for non-playing room {}",
-                room_id
-            );
+        if let Err(rejection) = room.authorize(socket.id.as_str(), GameAction::MatchTiles) {
+            reject_action(&socket, rejection);
             return;
         }
         let board = room.get_mut_memory_board();
         let tiles = board.get_flips();
         let matched = board.match_tiles();
-        if matched.get_matches().len() != 0 {
+        if let Some(entitlement) = RemovalEntitlement::from_match(&matched) {
+            room.advance_phase(PhaseEvent::TilesMatched);
+            // Send the squares the player can clear along with the matched piece
+            let squares: Vec<String> = room
+                .get_chess_board()
+                .map(|board| legal_removals(&board, &entitlement))
+                .unwrap_or_default()
+                .iter()
+                .map(|sq| sq.to_string())
+                .collect();
+            room.grant_removal(entitlement);
             socket
-                .emit("select_piece", matched.get_tile())
+                .emit("select_piece", (matched.get_tile(), squares))
                 .unwrap_or_else(|e| error!("Failed to get tile: {}", e));
             socket
                 .within(room_id.clone())
                 .emit("tiles_matched", (matched, socket.id.to_string()))
                 .unwrap_or_else(|e| error!("Failed to emit tiles_matched event: {}", e));
         } else {
+            room.advance_phase(PhaseEvent::TilesMismatched);
             socket
                 .within(room_id.clone())
                 .emit("unflip_tiles", tiles)
                 .unwrap_or_else(|e| error!("Failed to emit unflip_tiles event: {}", e));
         }
+        audit_memory_board(&room_id, room);
     }
+    state.save(&room_id).await;
 }
 
 pub async fn on_get_memory_board(socket: SocketRef, state: State<SocketState>) {
     let room_id = get_data_from_extension(&socket);
     // Get read access to the room
     if let Some(room) = state.rooms.read().await.get(&room_id) {
-        let board = room.get_memory_board();
+        let board = room.get_memory_board().redacted();
         socket
             .emit("memory_board", board)
             .unwrap_or_else(|e| error!("Failed to emit memory_board event: {}", e));
@@ -96,20 +108,23 @@ pub async fn on_match_piece(
     let room_id = get_data_from_extension(&socket);
 
     if let Some(room) = state.rooms.write().await.get_mut(&room_id) {
-        if room.get_state() != RoomState::Playing {
-            error!(
-                "Received match_piece event for non-playing room {}",
-                room_id
-            );
+        if let Err(rejection) = room.authorize(socket.id.as_str(), GameAction::MatchPiece) {
+            reject_action(&socket, rejection);
+            return;
+        }
+        // Matching a pair of your own pieces lets you keep the piece
+        // The tiles are already gone, so this only gives up the pending removal
+        if let Err(e) = room.decline_removal(socket.id.as_str(), &piece) {
+            socket
+                .emit("match_piece_failed", e)
+                .unwrap_or_else(|e| error!("Failed to emit match_piece_failed event: {}", e));
             return;
         }
-        // Remove 2 tiles corresponding to the given piece
-        let board = room.get_mut_memory_board();
-        let matches = board.remove_tiles(piece.clone());
-        let matched = MatchedTiles::new(piece, matches);
         socket
             .within(room_id.clone())
-            .emit("tiles_matched", (matched, socket.id.to_string()))
-            .unwrap_or_else(|e| error!("Failed to emit remove_tiles event: {}", e));
+            .emit("removal_declined", (piece, socket.id.to_string()))
+            .unwrap_or_else(|e| error!("Failed to emit removal_declined event: {}", e));
+        audit_memory_board(&room_id, room);
     }
+    state.save(&room_id).await;
 }
@@ -1,12 +1,16 @@
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
-use serde_json::json;
 use socketioxide::extract::{Data, SocketRef, State};
 use tracing::{error, info};
 
 use crate::{
-    room::{Room, RoomState, RoomType},
-    socket::state::SocketState,
-    util::get_data_from_extension,
+    clock::TimeControl,
+    room::{abandon::AbandonPolicy, color::ColorAssignment, Room, RoomState, RoomType},
+    socket::{
+        abandon::{await_opponent, handle_departure},
+        clock::start_turn_timers,
+        state::SocketState,
+    },
+    util::{get_account_from_extension, get_data_from_extension},
 };
 
 // Struct to represent join_room params
@@ -17,6 +21,7 @@ pub struct JoinRoom {
     avatar: String,
     avatar_orientation: u8,
     avatar_color: String,
+    token: Option<String>,
 }
 
 #[derive(Debug, Clone, serde::Deserialize)]
@@ -25,7 +30,14 @@ pub struct CreateRoom {
     avatar: String,
     avatar_orientation: u8,
     avatar_color: String,
-    time: Option<u64>,
+    time: Option<TimeControl>,
+    memory_time: Option<u64>,
+    #[serde(default)]
+    abandon: AbandonPolicy,
+    #[serde(default)]
+    color: ColorAssignment,
+    #[serde(default)]
+    rated: bool,
 }
 
 pub async fn on_create_room(
@@ -33,18 +45,14 @@ pub async fn on_create_room(
     state: State<SocketState>,
     Data::<CreateRoom>(p1): Data<CreateRoom>,
 ) {
+    // Rooms nobody came back to after a restart are cleared out once their seats expire
+    state.prune_abandoned().await;
+
     // Check if player is already in a room
     let room_id = get_data_from_extension(&socket);
-    if room_id != "" {
+    if !room_id.is_empty() {
         // Disconnect player from existing room
-        if let Some(mut room) = state.get(room_id.clone()).await {
-            room.disconnect_player(socket.id.to_string());
-            if room.player_count() == 0 {
-                state.remove(room_id.clone()).await;
-            } else {
-                state.update(room_id.clone(), room).await;
-            }
-        }
+        depart(&socket, state.0, &room_id).await;
     }
 
     // Generate a random room ID
@@ -58,39 +66,55 @@ pub async fn on_create_room(
     // Join the socket to the generated room ID
     socket.join(room_id.clone()).unwrap_or_else(|e| {
         error!("Error joining room: {:?}", e);
-        return;
     });
 
     // Insert the room ID into the socket extensions for easy access
     socket.extensions.insert(room_id.clone());
 
-    let room_type = if let Some(t) = p1.time {
-        RoomType::Timed(t)
+    let room_type = if let Some(control) = p1.time {
+        RoomType::Timed(control)
     } else {
         RoomType::Casual
     };
 
-    // Send the generated room ID back to the client
-    socket
-        .emit("room_joined", (room_id.clone(), room_type.to_string()))
-        .unwrap_or_else(|e| {
-            error!("Error sending roomCreated event: {:?}", e);
-            return;
-        });
-
-    // Create a new room in the state
-    let new_room = Room::new(
+    // Create a new room in the state, its seed decides every random choice on the memory board
+    let seed = thread_rng().gen();
+    let mut new_room = Room::new(
         socket.id.clone().to_string(),
         p1.name,
         p1.avatar,
         p1.avatar_orientation,
         p1.avatar_color,
         room_type,
+        seed,
     );
+    new_room.set_memory_time(p1.memory_time);
+    new_room.set_abandon_policy(p1.abandon);
+    new_room.set_color_assignment(p1.color);
+    new_room.set_rated(p1.rated);
+    new_room.set_account(socket.id.as_str(), get_account_from_extension(&socket));
     info!("Created room {:?}", new_room.clone());
+
+    // Send the generated room ID back to the client, with the token that gets them back in
+    let seat = new_room.get_seat(socket.id.as_str()).unwrap_or_default();
+    socket
+        .emit(
+            "room_joined",
+            (
+                room_id.clone(),
+                new_room.get_type().to_string(),
+                state.sessions.issue(&room_id, &seat),
+            ),
+        )
+        .unwrap_or_else(|e| {
+            error!("Error sending roomCreated event: {:?}", e);
+        });
     state.add(room_id.clone(), new_room).await;
 
-    info!("{} created and joined room {}", socket.id, room_id);
+    info!(
+        "{} created and joined room {} with seed {}",
+        socket.id, room_id, seed
+    );
 }
 
 pub async fn on_join_room(
@@ -105,40 +129,55 @@ pub async fn on_join_room(
         // If the player is already in the room, do nothing
         return;
     }
-    if rid != "" {
+    if !rid.is_empty() {
         // Disconnect player from existing room
-        if let Some(mut room) = state.get(rid.clone()).await {
-            room.disconnect_player(socket.id.to_string());
-            if room.player_count() == 0 {
-                state.remove(rid.clone()).await;
-            } else {
-                state.update(rid.clone(), room).await;
-            }
-        }
+        depart(&socket, state.0, &rid).await;
     }
 
     // Check if the room exists and has only one player
     info!("Player {} is trying to join room {}", socket.id, room_id);
 
-    let players = socket.within(room_id.clone()).sockets().unwrap().len();
-    let room = state.get(room_id.clone()).await;
-    if room.is_some() && players <= 1 {
-        let mut room = room.unwrap();
+    // Only a token signed for this room can claim a seat in it
+    let seat = data
+        .token
+        .as_deref()
+        .and_then(|token| state.sessions.verify(token))
+        .filter(|session| session.room_id == room_id)
+        .map(|session| session.seat);
 
-        // Add the second player to the room
-        socket.join(room_id.clone()).unwrap_or_else(|e| {
-            error!("Error joining room: {:?}", e);
-            return;
-        });
+    let players = socket.within(room_id.clone()).sockets().unwrap().len();
+    // The player is seated in place, so the room's timers can't overwrite the join
+    let mut rooms = state.rooms.write().await;
+    let room = rooms.get_mut(&room_id).filter(|_| players <= 1);
+    if let Some(room) = room {
         // Update the state to add the second player to the room
-        room.connect_player(
+        let Some(seat) = room.connect_player(
             socket.id.to_string(),
             data.name,
             data.avatar,
             data.avatar_orientation,
             data.avatar_color,
-        );
-        state.update(room_id.clone(), room.clone()).await;
+            seat,
+        ) else {
+            join_failed(&socket);
+            return;
+        };
+        // Logged in players play under their account, a reconnect keeps the seat's account
+        if let Some(account) = get_account_from_extension(&socket) {
+            room.set_account(socket.id.as_str(), Some(account));
+        }
+        socket.join(room_id.clone()).unwrap_or_else(|e| {
+            error!("Error joining room: {:?}", e);
+        });
+        // A suspended game picks up where it left off
+        if room.get_state() == RoomState::Playing {
+            room.resume_clocks().await;
+            start_turn_timers(&socket, state.0, &room_id, room);
+        }
+        await_opponent(&socket, state.0, &room_id, room);
+        let room = room.clone();
+        drop(rooms);
+        state.save(&room_id).await;
 
         // Insert the room ID into the socket extensions for easy access
         socket.extensions.insert(room_id.clone());
@@ -153,18 +192,20 @@ pub async fn on_join_room(
                 .emit("turn", (turn, times))
                 .unwrap_or_else(|e| {
                     error!("Error sending turn event: {:?}", e);
-                    return;
                 });
         }
 
         socket
             .emit(
                 "room_joined",
-                (room_id.clone(), room.get_type().to_string()),
+                (
+                    room_id.clone(),
+                    room.get_type().to_string(),
+                    state.sessions.issue(&room_id, &seat),
+                ),
             )
             .unwrap_or_else(|e| {
                 error!("Error sending room_joined event: {:?}", e);
-                return;
             });
 
         // Send player joined event to room
@@ -174,25 +215,55 @@ pub async fn on_join_room(
                 .emit("room_full", room.get_state())
                 .unwrap_or_else(|e| {
                     error!("Error sending player_joined event: {:?}", e);
-                    return;
                 });
         }
 
+        // Colors are known as soon as the room is ready, before anyone moves
+        if let (RoomState::Ready, Some(white)) = (room.get_state(), room.get_turn()) {
+            socket
+                .within(room_id.clone())
+                .emit("white", white)
+                .unwrap_or_else(|e| error!("Failed to emit white event: {}", e));
+        }
+
         info!("Player {} joined room {}", socket.id, &room_id);
     } else {
-        // Send an error message if the room is full or doesn't exist
-        socket
-            .emit("join_failed", "Room is full or does not exist")
-            .unwrap_or_else(|e| {
-                error!("Error sending error_message event: {:?}", e);
-                return;
-            })
+        join_failed(&socket);
     }
 }
 
+async fn depart(socket: &SocketRef, state: &'static SocketState, room_id: &str) {
+    // Disconnects the sender from a room in place, an empty room is removed from the state
+    let departed = match state.rooms.write().await.get_mut(room_id) {
+        Some(room) => {
+            let departure = room.disconnect_player(socket.id.to_string()).await;
+            Some((room.clone(), departure))
+        }
+        None => None,
+    };
+    let Some((room, departure)) = departed else {
+        return;
+    };
+    if room.player_count() == 0 {
+        state.remove(room_id.to_string()).await;
+    } else {
+        state.save(room_id).await;
+        handle_departure(socket, state, room_id, &room, departure);
+    }
+}
+
+fn join_failed(socket: &SocketRef) {
+    // Send an error message if the room is full or doesn't exist
+    socket
+        .emit("join_failed", "Room is full or does not exist")
+        .unwrap_or_else(|e| {
+            error!("Error sending error_message event: {:?}", e);
+        })
+}
+
 pub async fn on_leave_room(socket: SocketRef, state: State<SocketState>) {
     let room_id = get_data_from_extension(&socket);
-    if let Some(mut room) = state.get(room_id.clone()).await {
+    if state.rooms.read().await.contains_key(&room_id) {
         // Disconnect the player from the room
         socket
             .to(room_id.clone())
@@ -200,16 +271,8 @@ pub async fn on_leave_room(socket: SocketRef, state: State<SocketState>) {
             .unwrap_or_else(|e| {
                 error!("Error sending disconnection event: {:?}", e);
             });
-        room.disconnect_player(socket.id.to_string());
-        if room.player_count() == 0 {
-            // If the room is empty, remove it from the state
-            state.remove(room_id.clone()).await;
-        } else {
-            // Otherwise, update the state with the new room data
-            state.update(room_id.clone(), room).await;
-        }
-        // Clear extensions
-        socket.extensions.clear();
+        depart(&socket, state.0, &room_id).await;
+        // Clear the room from the extensions, the player stays logged into their account
         socket.extensions.insert(format!("{}|", socket.id));
 
         // Leave the room
@@ -225,11 +288,11 @@ pub async fn on_room_info(socket: SocketRef, state: State<SocketState>) {
     if let Some(room) = state.get(room_id.clone()).await {
         // Send the room info to the client
         socket
-            .emit("room_info", json!(room))
+            .emit("room_info", room.info())
             .unwrap_or_else(|e| error!("Error sending room_info event: {:?}", e));
     } else {
         socket
-            .emit("room_info", {})
+            .emit("room_info", ())
             .unwrap_or_else(|e| error!("Error sending room_info event: {:?}", e));
     }
 }
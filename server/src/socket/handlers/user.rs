@@ -1,7 +1,10 @@
 use socketioxide::extract::{SocketRef, State};
 use tracing::{error, warn};
 
-use crate::{chess::util::GameResult, socket::state::SocketState, util::get_data_from_extension};
+use crate::{
+    socket::{record::record_game, state::SocketState},
+    util::get_data_from_extension,
+};
 
 pub async fn on_player_info(socket: SocketRef, state: State<SocketState>) {
     // Get room id and check if player is in a room
@@ -13,7 +16,7 @@ pub async fn on_player_info(socket: SocketRef, state: State<SocketState>) {
         return;
     }
     let room = state.get(room_id.clone()).await;
-    if !room.is_some() {
+    if room.is_none() {
         socket
             .emit("player_info", "Room not found")
             .unwrap_or_else(|e| error!("Failed to emit player_info event: {}", e));
@@ -38,7 +41,7 @@ pub async fn on_player_info(socket: SocketRef, state: State<SocketState>) {
 pub async fn on_get_player_times(socket: SocketRef, state: State<SocketState>) {
     let room_id = get_data_from_extension(&socket);
     let room = state.get(room_id.clone()).await;
-    if !room.is_some() {
+    if room.is_none() {
         warn!("Room not found for get_player_times");
         return;
     }
@@ -53,48 +56,23 @@ pub async fn on_get_player_times(socket: SocketRef, state: State<SocketState>) {
 }
 
 pub async fn on_timeout(socket: SocketRef, state: State<SocketState>) {
+    // Ends the game if a player has run out of time
+    // The room's clock task normally gets there first, then this does nothing
     let room_id = get_data_from_extension(&socket);
-    let room = state.get(room_id.clone()).await;
-    if !room.is_some() {
-        warn!("Room not found for get_player_times");
-        return;
-    }
-
-    let room = room.unwrap();
-
-    // Check if both players have time left
-    if !room.timeout().await {
-        return;
-    }
-
-    let times = room.get_player_times().await;
-
-    // Get player data
-    let players = room.get_players();
-    // P1 is winner by default
-    let p1 = players.0;
-    let p2 = players.1;
-
-    if p1.is_none() || p2.is_none() {
-        return;
-    }
-
-    let mut p1 = p1.unwrap();
-    let mut p2 = p2.unwrap();
-
-    // Swap p1 and p2 if p2 has time leff
-    if times.1 > 0 {
-        std::mem::swap(&mut p1, &mut p2);
-    }
-
-    let result = GameResult {
-        player1: p1,
-        player2: p2,
-        result: "timeout".to_string(),
+    let result = match state.rooms.write().await.get_mut(&room_id) {
+        Some(room) => room.flag_fall().await,
+        None => {
+            warn!("Room not found for timeout");
+            return;
+        }
     };
+    state.save(&room_id).await;
 
-    socket
-        .within(room_id)
-        .emit("timeout", result)
-        .unwrap_or_else(|e| error!("Failed to emit player_times event: {}", e));
+    if let Some(result) = result {
+        socket
+            .within(room_id.clone())
+            .emit("timeout", &result)
+            .unwrap_or_else(|e| error!("Failed to emit timeout event: {}", e));
+        record_game(&socket, &state, &room_id, &result).await;
+    }
 }
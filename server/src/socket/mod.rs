@@ -2,9 +2,16 @@ use socketioxide::extract::{SocketRef, State};
 use tokio::time::{sleep, Duration};
 use tracing::{error, info};
 
-use crate::{socket::state::SocketState, util::get_data_from_extension};
+use crate::{
+    session::SESSION_TTL,
+    socket::{abandon::handle_departure, state::SocketState},
+    util::get_data_from_extension,
+};
 
+pub mod abandon;
+pub mod clock;
 pub mod handlers;
+pub mod record;
 pub mod state;
 
 pub async fn on_connect(socket: SocketRef) {
@@ -22,8 +29,25 @@ pub async fn on_connect(socket: SocketRef) {
     socket.on("leave_room", handlers::room::on_leave_room);
     socket.on("room_info", handlers::room::on_room_info);
 
+    // Account Events
+    socket.on("register", handlers::account::on_register);
+    socket.on("login", handlers::account::on_login);
+    socket.on("logout", handlers::account::on_logout);
+    socket.on("update_account", handlers::account::on_update_account);
+    socket.on("player_profile", handlers::account::on_player_profile);
+    socket.on("player_games", handlers::account::on_player_games);
+    socket.on("leaderboard", handlers::account::on_leaderboard);
+
     // Game Events
     socket.on("reset_game", handlers::game::on_reset_game);
+    socket.on("rematch", handlers::game::on_rematch);
+    socket.on("resign", handlers::game::on_resign);
+    socket.on("offer_draw", handlers::game::on_offer_draw);
+    socket.on("accept_draw", handlers::game::on_accept_draw);
+    socket.on("decline_draw", handlers::game::on_decline_draw);
+    socket.on("request_takeback", handlers::game::on_request_takeback);
+    socket.on("accept_takeback", handlers::game::on_accept_takeback);
+    socket.on("decline_takeback", handlers::game::on_decline_takeback);
 
     // Memory Game Events
     socket.on("flip_tile", handlers::memory::on_flip_tile);
@@ -44,17 +68,31 @@ pub async fn on_connect(socket: SocketRef) {
     socket.on("get_player_times", handlers::user::on_get_player_times);
     socket.on("timeout", handlers::user::on_timeout);
 
+    // Admin events
+    socket.on("audit_room", handlers::admin::on_audit_room);
+
     // Debug events
+    // rooms dumps every hidden memory board, so it is only served by debug builds
     socket.on("message", handlers::debug::on_message);
     socket.on("extensions", handlers::debug::on_extensions);
-    socket.on("rooms", handlers::debug::on_rooms);
+    if cfg!(debug_assertions) {
+        socket.on("rooms", handlers::debug::on_rooms);
+    }
 
     socket.on_disconnect(on_disconnect)
 }
 
 pub async fn on_disconnect(socket: SocketRef, state: State<SocketState>) {
     let room_id = get_data_from_extension(&socket);
-    if let Some(mut room) = state.get(room_id.clone()).await {
+    // The player is disconnected in place, so the room's timers can't overwrite the departure
+    let departed = match state.rooms.write().await.get_mut(&room_id) {
+        Some(room) => {
+            let departure = room.disconnect_player(socket.id.to_string()).await;
+            Some((room.clone(), departure))
+        }
+        None => None,
+    };
+    if let Some((room, departure)) = departed {
         // Disconnect the player from the room
         socket
             .to(room_id.clone())
@@ -62,19 +100,13 @@ pub async fn on_disconnect(socket: SocketRef, state: State<SocketState>) {
             .unwrap_or_else(|e| {
                 error!("Error sending disconnection event: {:?}", e);
             });
-        room.disconnect_player(socket.id.to_string());
-        state.update(room_id.clone(), room.clone()).await;
+        state.save(&room_id).await;
+        handle_departure(&socket, state.0, &room_id, &room, departure);
         if room.player_count() == 0 {
-            // If the room is empty, wait 2 minutes before removing it from the state
-            sleep(Duration::from_secs(120)).await;
-            // If the room is still empty after 1 minute, remove it from the state
-            let room = state.get(room_id.clone()).await;
-            if let Some(room) = room {
-                if room.player_count() == 0 {
-                    state.remove(room_id.clone()).await;
-                    info!("Room {} removed", room_id);
-                }
-            }
+            // An empty room is kept as long as the players' tokens hold their seats
+            // Once every seat has expired nobody can get back in, and the room is removed
+            sleep(Duration::from_secs(SESSION_TTL)).await;
+            state.prune_abandoned().await;
         }
     }
     info!("Player {} disconnected", socket.id);
@@ -0,0 +1,66 @@
+use socketioxide::extract::SocketRef;
+use tracing::{error, info};
+
+use crate::{chess::util::GameResult, socket::state::SocketState};
+
+pub async fn record_game(
+    socket: &SocketRef,
+    state: &SocketState,
+    room_id: &str,
+    result: &GameResult,
+) {
+    // Adds a finished game to the stats and history of its players' accounts
+    let (moves, pool) = match state.rooms.read().await.get(room_id) {
+        Some(room) => (
+            room.get_plies(),
+            room.is_rated().then(|| room.get_rating_pool()),
+        ),
+        None => (0, None),
+    };
+    state
+        .accounts
+        .record_game(result, moves)
+        .unwrap_or_else(|e| {
+            error!("Failed to record game: {}", e);
+            false
+        });
+
+    // Rated rooms also move both ratings in the room's pool, when both players have accounts
+    let Some(pool) = pool else {
+        return;
+    };
+    let (winner, loser) = (&result.player1, &result.player2);
+    let (Some(a1), Some(a2)) = (winner.get_account(), loser.get_account()) else {
+        return;
+    };
+    if a1 == a2 {
+        return;
+    }
+    let score = if result.is_draw() { 0.5 } else { 1.0 };
+    let (d1, d2) = match state.accounts.rate_game(pool, a1, a2, score) {
+        Ok(deltas) => deltas,
+        Err(e) => {
+            error!("Failed to rate game in room {}: {}", room_id, e);
+            return;
+        }
+    };
+    info!(
+        "Rated game in room {}: {:+} and {:+}",
+        room_id, d1.change, d2.change
+    );
+
+    // Show the rating changes on the game over screen
+    let info = match state.rooms.write().await.get_mut(room_id) {
+        Some(room) => {
+            room.set_rating_change(&winner.get_id(), d1);
+            room.set_rating_change(&loser.get_id(), d2);
+            room.info()
+        }
+        None => return,
+    };
+    state.save(room_id).await;
+    socket
+        .within(room_id.to_string())
+        .emit("room_info", info)
+        .unwrap_or_else(|e| error!("Failed to emit room_info event: {}", e));
+}
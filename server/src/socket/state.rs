@@ -1,38 +1,116 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use tokio::sync::RwLock;
+use tracing::{error, info};
 
-use crate::room::Room;
+use crate::{
+    accounts::AccountStore,
+    room::Room,
+    session::SessionSigner,
+    snapshots::{RoomStore, SnapshotWriter},
+};
 
 pub struct SocketState {
     // Main state of the app
     // Stores Room objects for each active room
     // RwLock ensures safe concurrent mutability and access across threads
     pub rooms: RwLock<HashMap<String, Room>>,
+    // Signs the tokens players use to get back into their seats
+    pub sessions: SessionSigner,
+    // Player accounts and the games they finished
+    pub accounts: Arc<AccountStore>,
+    // Snapshots of the rooms, reloaded on startup
+    snapshots: Arc<RoomStore>,
+    // Queue of snapshot writes, written in order off the async runtime
+    writer: SnapshotWriter,
 }
 
 impl SocketState {
     pub async fn new() -> Self {
-        // Returns a new empty SocketState
-        Self {
+        // Returns the state saved by the last run, or a new empty SocketState
+        let accounts = AccountStore::from_env().expect("Failed to open the account database");
+        let snapshots = RoomStore::from_env().expect("Failed to open the room store");
+        Self::with_stores(accounts, Arc::new(snapshots)).await
+    }
+    pub async fn with_stores(accounts: AccountStore, snapshots: Arc<RoomStore>) -> Self {
+        // Builds the state on top of the given stores and reloads the rooms they kept
+        let key = accounts
+            .signing_key()
+            .expect("Failed to read the signing key");
+        let state = Self {
             rooms: RwLock::new(HashMap::new()),
+            sessions: SessionSigner::from_env(key),
+            accounts: Arc::new(accounts),
+            writer: SnapshotWriter::spawn(snapshots.clone()),
+            snapshots,
+        };
+        state.restore_rooms().await;
+        state
+    }
+    async fn restore_rooms(&self) {
+        // Loads every saved room, players get back in with their session tokens
+        let saved = self.snapshots.load_all().unwrap_or_else(|e| {
+            error!("Failed to load saved rooms: {}", e);
+            vec![]
+        });
+        for (room_id, snapshot) in saved {
+            match serde_json::from_str::<Room>(&snapshot) {
+                Ok(mut room) => {
+                    room.restore().await;
+                    self.add(room_id, room).await;
+                }
+                Err(e) => {
+                    // A snapshot from an incompatible version can't be resumed
+                    error!("Dropping unreadable snapshot of room {}: {}", room_id, e);
+                    self.forget(&room_id);
+                }
+            }
         }
+        info!("Restored {} rooms", self.rooms.read().await.len());
     }
     pub async fn add(&self, room_id: String, room: Room) {
         // Adds a new Room to the state with a given room_id
         let mut map = self.rooms.write().await;
+        self.persist(&room_id, &room).await;
         map.insert(room_id, room);
     }
-    pub async fn update(&self, room_id: String, room: Room) {
-        // Updates a Room in the state with a given room_id
-        let mut map = self.rooms.write().await;
-        map.insert(room_id, room);
+    pub async fn save(&self, room_id: &str) {
+        // Saves a Room that was changed in place, through the rooms lock
+        // Snapshots are queued under the lock, so the last one queued is the room as it is now
+        let map = self.rooms.read().await;
+        if let Some(room) = map.get(room_id) {
+            self.persist(room_id, room).await;
+        }
     }
     pub async fn remove(&self, room_id: String) {
         // Removes a Room from the state with a given room_id
         let mut map = self.rooms.write().await;
+        self.forget(&room_id);
         map.remove(&room_id);
     }
+    pub async fn prune_abandoned(&self) {
+        // Removes the rooms nobody came back to, e.g. after a restart
+        let mut map = self.rooms.write().await;
+        map.retain(|room_id, room| {
+            if room.is_abandoned() {
+                info!("Removing abandoned room {}", room_id);
+                self.forget(room_id);
+            }
+            !room.is_abandoned()
+        });
+    }
+    async fn persist(&self, room_id: &str, room: &Room) {
+        // Queues a snapshot of the room, clocks included
+        let mut room = room.clone();
+        room.save_clocks().await;
+        match serde_json::to_string(&room) {
+            Ok(snapshot) => self.writer.save(room_id, snapshot),
+            Err(e) => error!("Failed to serialize room {}: {}", room_id, e),
+        }
+    }
+    fn forget(&self, room_id: &str) {
+        self.writer.delete(room_id);
+    }
     pub async fn get(&self, room_id: String) -> Option<Room> {
         // Returns a clone of the Room with a given room_id
         let map = self.rooms.read().await;
@@ -44,3 +122,98 @@ impl SocketState {
         map.values().cloned().collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        room::{RoomState, RoomType},
+        session::SESSION_TTL,
+    };
+
+    async fn empty_state(snapshots: Arc<RoomStore>) -> SocketState {
+        let accounts = AccountStore::open_in_memory().unwrap();
+        SocketState::with_stores(accounts, snapshots).await
+    }
+
+    #[tokio::test]
+    async fn rooms_survive_a_restart() {
+        let state = empty_state(Arc::new(RoomStore::open_in_memory().unwrap())).await;
+        let room = Room::new(
+            "p1".to_string(),
+            "Alice".to_string(),
+            "a".to_string(),
+            0,
+            "red".to_string(),
+            RoomType::Casual,
+            42,
+        );
+        state.add("ABCDEF".to_string(), room.clone()).await;
+        state.add("GHIJKL".to_string(), room).await;
+        state.remove("GHIJKL".to_string()).await;
+        state.snapshots.save("BROKEN", "{").unwrap();
+        state.writer.flush();
+
+        // The new state reads the same store, like a restarted server
+        let SocketState { snapshots, .. } = state;
+        let state = empty_state(snapshots).await;
+        let restored = state.get("ABCDEF".to_string()).await.unwrap();
+        assert_eq!(restored.get_state(), RoomState::Waiting);
+        assert_eq!(restored.player_count(), 0);
+        assert!(state.get("GHIJKL".to_string()).await.is_none());
+        state.writer.flush();
+        assert_eq!(state.snapshots.load_all().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn snapshots_follow_changes_made_in_place() {
+        let snapshots = Arc::new(RoomStore::open_in_memory().unwrap());
+        let state = empty_state(snapshots.clone()).await;
+        let room = Room::new(
+            "p1".to_string(),
+            "Alice".to_string(),
+            "a".to_string(),
+            0,
+            "red".to_string(),
+            RoomType::Casual,
+            42,
+        );
+        state.add("ABCDEF".to_string(), room).await;
+        for rated in [true, false, true] {
+            if let Some(room) = state.rooms.write().await.get_mut("ABCDEF") {
+                room.set_rated(rated);
+            }
+            state.save("ABCDEF").await;
+        }
+
+        state.writer.flush();
+        let saved = snapshots.load_all().unwrap();
+        let room: Room = serde_json::from_str(&saved[0].1).unwrap();
+        assert!(room.is_rated());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn empty_rooms_last_as_long_as_their_seats() {
+        let state = empty_state(Arc::new(RoomStore::open_in_memory().unwrap())).await;
+        let room = Room::new(
+            "p1".to_string(),
+            "Alice".to_string(),
+            "a".to_string(),
+            0,
+            "red".to_string(),
+            RoomType::Casual,
+            42,
+        );
+        state.add("ABCDEF".to_string(), room).await;
+        if let Some(room) = state.rooms.write().await.get_mut("ABCDEF") {
+            room.disconnect_player("p1".to_string()).await;
+        }
+
+        tokio::time::advance(tokio::time::Duration::from_secs(SESSION_TTL - 1)).await;
+        state.prune_abandoned().await;
+        assert!(state.get("ABCDEF".to_string()).await.is_some());
+        tokio::time::advance(tokio::time::Duration::from_secs(1)).await;
+        state.prune_abandoned().await;
+        assert!(state.get("ABCDEF".to_string()).await.is_none());
+    }
+}
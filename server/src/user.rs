@@ -1,7 +1,14 @@
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use tokio::time::Duration;
-use tokio::{sync::Mutex, task::JoinHandle, time::sleep};
+use tokio::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    clock::{Clock, TimeControl},
+    session::{new_seat, SESSION_TTL},
+};
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct User {
@@ -12,10 +19,14 @@ pub struct User {
     avatar_color: String,
     chess_color: String,
     connected: bool,
+    seat: String,         // id of the player's seat, signed into their session token
+    account: Option<i64>, // id of the player's account, None for anonymous players
+    #[serde(default)]
+    time_left: Option<u64>, // milliseconds left on the clock when the room was last saved
     #[serde(skip)]
-    time: Arc<Mutex<u64>>, // time left in seconds
+    held_until: Option<Instant>, // while disconnected, when the seat's token stops being valid
     #[serde(skip)]
-    timer_handle: Arc<Mutex<Option<JoinHandle<()>>>>,
+    clock: Arc<Mutex<Clock>>,
 }
 
 impl User {
@@ -26,7 +37,7 @@ impl User {
         avatar_orientation: u8,
         avatar_color: String,
         chess_color: String,
-        initial_time: u64,
+        time_control: TimeControl,
     ) -> Self {
         Self {
             id,
@@ -36,8 +47,11 @@ impl User {
             avatar_color,
             chess_color,
             connected: true,
-            time: Arc::new(Mutex::new(initial_time)),
-            timer_handle: Arc::new(Mutex::new(None)),
+            seat: new_seat(),
+            account: None,
+            time_left: None,
+            held_until: None,
+            clock: Arc::new(Mutex::new(Clock::new(time_control))),
         }
     }
 
@@ -54,45 +68,40 @@ impl User {
     }
 
     pub async fn get_time(&self) -> u64 {
-        *self.time.lock().await
+        // Returns the time left in milliseconds
+        self.clock.lock().await.remaining_ms()
     }
 
     pub async fn start_turn(&self) {
-        let time = self.time.clone();
-        let mut handle = self.timer_handle.lock().await;
+        self.clock.lock().await.start();
+    }
 
-        // Abort any existing timer
-        if let Some(handle) = handle.take() {
-            handle.abort();
-        }
+    pub async fn end_turn(&self) {
+        self.clock.lock().await.stop();
+    }
 
-        // Start a new timer
-        *handle = Some(tokio::spawn(async move {
-            loop {
-                sleep(Duration::from_secs(1)).await;
-                let mut time = time.lock().await;
-                if *time > 0 {
-                    *time -= 1;
-                } else {
-                    break;
-                }
-            }
-        }));
+    pub async fn complete_move(&self) {
+        // Ends the turn after a move, applying the room's increment and delay
+        self.clock.lock().await.complete_move();
     }
 
-    pub async fn end_turn(&self) {
-        let mut handle = self.timer_handle.lock().await;
-        if let Some(handle) = handle.take() {
-            handle.abort();
-        }
+    pub async fn reset_time(&self) {
+        // Sets the clock back to the base time
+        self.clock.lock().await.reset();
     }
 
-    pub async fn reset_time(&self, time: u64) {
-        *self.time.lock().await = time;
+    pub async fn set_time(&self, ms: u64) {
+        // Stops the clock and sets the time left in milliseconds
+        self.clock
+            .lock()
+            .await
+            .set_remaining(Duration::from_millis(ms));
     }
 
     pub fn disconnect(&mut self) {
+        // The seat stays reserved for the player's token for a while
         self.connected = false;
+        self.held_until = Some(Instant::now() + Duration::from_secs(SESSION_TTL));
     }
 
     pub fn is_connected(&self) -> bool {
@@ -101,19 +110,44 @@ impl User {
 
     pub fn reconnect(&mut self, id: String) {
         self.connected = true;
+        self.held_until = None;
         self.id = id;
     }
 
-    pub fn matches(
-        &self,
-        name: &String,
-        avatar: &String,
-        avatar_orientation: &u8,
-        avatar_color: &String,
-    ) -> bool {
-        self.name == *name
-            && self.avatar == *avatar
-            && self.avatar_orientation == *avatar_orientation
-            && self.avatar_color == *avatar_color
+    pub fn get_seat(&self) -> String {
+        self.seat.clone()
+    }
+
+    pub fn get_account(&self) -> Option<i64> {
+        self.account
+    }
+
+    pub fn set_account(&mut self, account: Option<i64>) {
+        self.account = account;
+    }
+
+    pub fn holds_seat(&self, seat: &str) -> bool {
+        // Returns true if a token for the given seat still belongs to this player
+        self.seat == seat && !self.seat_expired()
+    }
+
+    pub fn seat_expired(&self) -> bool {
+        // Returns true if the player is gone and their token no longer keeps the seat
+        !self.connected && self.held_until.is_some_and(|t| Instant::now() >= t)
+    }
+
+    pub async fn save_time(&mut self) {
+        // Copies the time left into the serialized fields, the clock itself isn't serialized
+        self.time_left = Some(self.get_time().await);
+    }
+
+    pub async fn restore(&mut self, time_control: TimeControl) {
+        // Rebuilds the clock of a player loaded from a snapshot, stopped at the saved time
+        // Their socket is gone, so the seat is held for their token like after a disconnect
+        self.clock = Arc::new(Mutex::new(Clock::new(time_control)));
+        if let Some(ms) = self.time_left.take() {
+            self.set_time(ms).await;
+        }
+        self.disconnect();
     }
 }
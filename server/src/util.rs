@@ -1,5 +1,10 @@
 use socketioxide::extract::SocketRef;
-use tracing::error;
+use tracing::{error, warn};
+
+use crate::{
+    accounts::AccountId,
+    room::{action::ActionRejected, Room},
+};
 
 pub fn get_data_from_extension(socket: &SocketRef) -> String {
     match socket.extensions.get::<String>() {
@@ -10,3 +15,42 @@ pub fn get_data_from_extension(socket: &SocketRef) -> String {
         Some(s) => s.to_string(),
     }
 }
+
+pub fn get_account_from_extension(socket: &SocketRef) -> Option<i64> {
+    // Returns the account the socket is logged into, None for anonymous players
+    socket.extensions.get::<AccountId>().map(|a| a.0)
+}
+
+pub fn reject_action(socket: &SocketRef, rejection: ActionRejected) {
+    // Tells the sender that their game action was refused
+    warn!(
+        "Rejected {:?} from {}: {:?}",
+        rejection.action, socket.id, rejection.reason
+    );
+    socket
+        .emit("action_rejected", rejection)
+        .unwrap_or_else(|e| error!("Failed to emit action_rejected event: {}", e));
+}
+
+pub fn audit_memory_board(room_id: &str, room: &Room) {
+    // Audits the memory board after every change in debug builds, mismatches are logged
+    if cfg!(debug_assertions) {
+        room.audit_memory(room_id);
+    }
+}
+
+pub fn database_path() -> String {
+    // Returns DATABASE_PATH, or memchess.db in the working directory if it isn't set
+    match std::env::var("DATABASE_PATH") {
+        Ok(path) if !path.is_empty() => path,
+        _ => "memchess.db".to_string(),
+    }
+}
+
+pub fn is_admin(token: &str) -> bool {
+    // Checks a token against ADMIN_TOKEN, admin commands are disabled when it isn't set
+    match std::env::var("ADMIN_TOKEN") {
+        Ok(admin) => !admin.is_empty() && admin == token,
+        Err(_) => false,
+    }
+}